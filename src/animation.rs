@@ -0,0 +1,1280 @@
+//! Animation processing functionality for FBX files.
+
+use crate::constraint::{bake_constraints, remove_constrained_tracks};
+use crate::error::FbxError;
+use crate::events::collect_markers;
+use crate::label::FbxAssetLabel;
+use crate::loader::{FbxAnimationSettings, FbxLoaderSettings};
+use crate::material::{bake_material_tracks, material_curve, rest_material_value};
+use crate::mesh::morph_target_weights;
+use crate::node::{animation_target_id, node_name};
+use crate::property::{bake_property_tracks, property_curve, rest_property_value};
+use crate::reduction::{reduce_animation, translation_units, FbxKeyReduction};
+use crate::retarget::{bind_animation, match_nodes_by_name, Retargeter};
+use crate::root_motion::extract_root_motion;
+use crate::types::{
+    FbxAnimation, FbxAnimationClip, FbxAnimationEvent, FbxAnimationMarker, FbxInterpolation,
+    FbxKeyframes, FbxMaterialTrack, FbxMorphTrack, FbxNodeTrack, FbxPropertyTrack, FbxRootMotion,
+};
+use crate::utils::{
+    convert_interpolation, convert_matrix, convert_quat, convert_transform, convert_vec3,
+};
+use bevy::animation::animatable::Animatable;
+use bevy::animation::animation_curves::{AnimatableCurve, AnimatableProperty, WeightsCurve};
+use bevy::animation::gltf_curves::{
+    CubicKeyframeCurve, SteppedKeyframeCurve, WideLinearKeyframeCurve,
+};
+use bevy::animation::graph::{AnimationGraph, AnimationNodeIndex};
+use bevy::animation::{animated_field, AnimationClip, AnimationTargetId, VariableCurve};
+use bevy::asset::{Handle, LoadContext};
+use bevy::math::curve::{ConstantCurve, Interval, UnevenSampleAutoCurve};
+use bevy::prelude::*;
+use std::collections::{BTreeSet, HashMap};
+use std::ops::{Add, Mul};
+
+/// Relative tolerance for treating Bezier handles as Hermite tangents.
+const TANGENT_TOLERANCE: f64 = 1e-3;
+
+/// Assets created from the animation stacks of an FBX scene.
+#[derive(Default)]
+pub struct FbxAnimationAssets {
+    pub animations: Vec<Handle<AnimationClip>>,
+    pub named_animations: HashMap<Box<str>, Handle<AnimationClip>>,
+    pub animation_stacks: Vec<Handle<AnimationGraph>>,
+    pub named_animation_stacks: HashMap<Box<str>, Handle<AnimationGraph>>,
+    pub animation_clips: Vec<FbxAnimationClip>,
+    pub root_motions: Vec<Option<Handle<FbxRootMotion>>>,
+    /// Keys removed by keyframe reduction, if enabled
+    pub key_reduction: Option<FbxKeyReduction>,
+    /// Converted animation of each stack, kept to sample poses
+    pub stack_animations: Vec<FbxAnimation>,
+    pub unmatched_bones: Vec<String>,
+}
+
+/// Process all animation stacks from the FBX scene.
+///
+/// Every stack is baked into a single clip. Stacks with several layers also
+/// get one clip per layer, combined by an [`AnimationGraph`] that reproduces
+/// the layer weights and blend modes. Clips configured in
+/// [`FbxAnimationSettings::clips`] are cut out of the baked stacks.
+///
+/// With [`FbxAnimationSettings::root_motion_joint`], the horizontal motion of
+/// that joint is moved out of the stack and clip animations into separate
+/// [`FbxRootMotion`] assets.
+///
+/// With a target `skeleton`, tracks target the node of the same name in the
+/// skeleton, so the clips play on scenes spawned from that file. Tracks of
+/// nodes missing from the skeleton are dropped and reported. With
+/// [`FbxAnimationSettings::retarget`], tracks are also converted to the rest
+/// pose of the skeleton.
+pub fn process_animations(
+    scene: &ufbx::Scene,
+    skeleton: Option<&ufbx::Scene>,
+    settings: &FbxLoaderSettings,
+    load_context: &mut LoadContext,
+) -> Result<FbxAnimationAssets, FbxError> {
+    let mut assets = FbxAnimationAssets::default();
+
+    let targets: Option<Vec<Option<AnimationTargetId>>> = skeleton.map(|skeleton| {
+        match_nodes_by_name(scene, skeleton)
+            .into_iter()
+            .map(|node| node.map(animation_target_id))
+            .collect()
+    });
+    let retargeter = match (skeleton, &settings.animation.retarget) {
+        (Some(skeleton), Some(retarget)) => {
+            Some(Retargeter::from_scenes(scene, skeleton, retarget))
+        }
+        (None, Some(_)) => {
+            warn!("Retargeting needs a target skeleton, animations are not retargeted");
+            None
+        }
+        _ => None,
+    };
+    let mut unmatched = BTreeSet::new();
+
+    let root_motion_node = settings
+        .animation
+        .root_motion_joint
+        .as_deref()
+        .and_then(|joint| {
+            let node = scene
+                .nodes
+                .as_ref()
+                .iter()
+                .find(|node| node_name(node) == joint);
+            if node.is_none() {
+                warn!(
+                    "Root motion joint '{}' not found, keeping motion in place",
+                    joint
+                );
+            }
+            // The tracks are local to the joint's parent, whose world
+            // transform includes the axis conversion of the scene root
+            node.map(|node| {
+                let parent_to_world = node.parent.as_ref().map_or(Mat4::IDENTITY, |parent| {
+                    convert_matrix(&parent.node_to_world)
+                });
+                (node.element.typed_id as usize, parent_to_world)
+            })
+        });
+
+    let meters_per_unit = translation_units(scene);
+
+    let frame_rate = if scene.settings.frames_per_second > 0.0 {
+        scene.settings.frames_per_second
+    } else {
+        settings.animation.sample_rate
+    };
+
+    for (index, stack) in scene.anim_stacks.as_ref().iter().enumerate() {
+        let mut animation = bake_anim_stack(scene, stack, &settings.animation)?;
+        if settings.animation.bake_constraints {
+            bake_constraints(scene, stack, &mut animation, settings.animation.sample_rate)?;
+        }
+        // Layer clips are blended together, so only the stack clip has events
+        animation.markers = collect_markers(stack, frame_rate, &settings.animation.event_prefix);
+        if let Some(retargeter) = &retargeter {
+            retargeter.retarget(&mut animation, &mut unmatched);
+        } else if let Some(targets) = &targets {
+            bind_animation(&mut animation, targets, &mut unmatched);
+        }
+        if let Some((node_index, parent_to_world)) = root_motion_node {
+            animation.root_motion =
+                extract_root_motion(&mut animation, node_index, parent_to_world);
+        }
+        if let Some(key_reduction) = &settings.animation.key_reduction {
+            *assets.key_reduction.get_or_insert_default() +=
+                reduce_animation(&mut animation, key_reduction, &meters_per_unit);
+        }
+        let clip = create_animation_clip(&animation);
+
+        let handle =
+            load_context.add_labeled_asset(FbxAssetLabel::Animation(index).to_string(), clip);
+        let root_motion = animation.root_motion.clone().map(|root_motion| {
+            load_context
+                .add_labeled_asset(FbxAssetLabel::RootMotion(index).to_string(), root_motion)
+        });
+
+        let layers = stack.layers.as_ref();
+        let graph = if layers.len() > 1 {
+            let mut layer_clips = Vec::new();
+            for (layer_index, layer) in layers.iter().enumerate() {
+                let mut layer_animation = bake_anim(
+                    scene,
+                    &layer.anim,
+                    layer.element.name.as_ref(),
+                    &settings.animation,
+                )?;
+                // Every layer that replaces the lower ones holds the baked
+                // constraints, so the blended stack is constrained as well
+                if settings.animation.bake_constraints {
+                    if layer.additive {
+                        remove_constrained_tracks(scene, &mut layer_animation);
+                    } else {
+                        bake_constraints(
+                            scene,
+                            stack,
+                            &mut layer_animation,
+                            settings.animation.sample_rate,
+                        )?;
+                    }
+                }
+                // Additive offsets are relative to the source rest pose, so
+                // they are only bound by name
+                match (&retargeter, &targets) {
+                    (Some(retargeter), _) if !layer.additive => {
+                        retargeter.retarget(&mut layer_animation, &mut unmatched);
+                    }
+                    (_, Some(targets)) => {
+                        bind_animation(&mut layer_animation, targets, &mut unmatched);
+                    }
+                    _ => {}
+                }
+                if layer.additive {
+                    make_additive(scene, &mut layer_animation);
+                } else if let Some((node_index, parent_to_world)) = root_motion_node {
+                    // Keep the layer in place like the stack clip
+                    extract_root_motion(&mut layer_animation, node_index, parent_to_world);
+                }
+                check_layer_support(stack, layer);
+                if let Some(key_reduction) = &settings.animation.key_reduction {
+                    *assets.key_reduction.get_or_insert_default() +=
+                        reduce_animation(&mut layer_animation, key_reduction, &meters_per_unit);
+                }
+
+                let layer_handle = load_context.add_labeled_asset(
+                    FbxAssetLabel::AnimationLayer(index, layer_index).to_string(),
+                    create_animation_clip(&layer_animation),
+                );
+                layer_clips.push((layer_handle, &**layer));
+            }
+
+            let mut graph = AnimationGraph::new();
+            let root = graph.root;
+            add_layers_to_graph(&mut graph, &layer_clips, 1.0, root);
+            graph
+        } else {
+            AnimationGraph::from_clip(handle.clone()).0
+        };
+
+        let graph_handle =
+            load_context.add_labeled_asset(FbxAssetLabel::AnimationStack(index).to_string(), graph);
+
+        if !stack.element.name.is_empty() {
+            assets
+                .named_animations
+                .insert(Box::from(stack.element.name.as_ref()), handle.clone());
+            assets
+                .named_animation_stacks
+                .insert(Box::from(stack.element.name.as_ref()), graph_handle.clone());
+        }
+
+        assets.animations.push(handle);
+        assets.animation_stacks.push(graph_handle);
+        assets.root_motions.push(root_motion);
+        assets.stack_animations.push(animation);
+    }
+
+    for (index, clip_settings) in settings.animation.clips.iter().enumerate() {
+        let Some((stack, animation)) = scene
+            .anim_stacks
+            .as_ref()
+            .get(clip_settings.stack)
+            .zip(assets.stack_animations.get(clip_settings.stack))
+        else {
+            warn!(
+                "Skipping clip '{}': animation stack {} does not exist",
+                clip_settings.name, clip_settings.stack
+            );
+            continue;
+        };
+        if clip_settings.end_frame < clip_settings.start_frame {
+            warn!(
+                "Skipping clip '{}': end frame {} is before start frame {}",
+                clip_settings.name, clip_settings.end_frame, clip_settings.start_frame
+            );
+            continue;
+        }
+
+        // Baked keys start at the beginning of the stack
+        let start = (clip_settings.start_frame as f64 / frame_rate - stack.time_begin) as f32;
+        let end = (clip_settings.end_frame as f64 / frame_rate - stack.time_begin) as f32;
+        let clip_animation = slice_animation(animation, &clip_settings.name, start, end);
+
+        let handle = load_context.add_labeled_asset(
+            FbxAssetLabel::AnimationClip(index).to_string(),
+            create_animation_clip(&clip_animation),
+        );
+        let root_motion = clip_animation.root_motion.map(|root_motion| {
+            load_context.add_labeled_asset(
+                FbxAssetLabel::AnimationClipRootMotion(index).to_string(),
+                root_motion,
+            )
+        });
+        assets
+            .named_animations
+            .insert(Box::from(clip_settings.name.as_str()), handle.clone());
+        assets.animation_clips.push(FbxAnimationClip {
+            name: Box::from(clip_settings.name.as_str()),
+            clip: handle,
+            looping: clip_settings.looping,
+            root_motion,
+        });
+    }
+
+    if let Some(reduction) = assets.key_reduction {
+        info!(
+            "Keyframe reduction kept {} of {} keys ({:.1}:1)",
+            reduction.keys_after,
+            reduction.keys_before,
+            reduction.ratio()
+        );
+    }
+
+    assets.unmatched_bones = unmatched
+        .into_iter()
+        .map(|node_index| node_name(&scene.nodes[node_index]))
+        .collect();
+    if !assets.unmatched_bones.is_empty() {
+        warn!(
+            "Animated nodes not found in the target skeleton: {}",
+            assets.unmatched_bones.join(", ")
+        );
+    }
+
+    Ok(assets)
+}
+
+/// Cut the keys between `start` and `end` out of an animation, with times
+/// relative to `start`.
+///
+/// Channels get interpolated keys at both ends so the clip starts and ends on
+/// the exact pose of the source animation.
+pub fn slice_animation(animation: &FbxAnimation, name: &str, start: f32, end: f32) -> FbxAnimation {
+    FbxAnimation {
+        name: name.to_string(),
+        duration: (end - start).max(0.0),
+        tracks: animation
+            .tracks
+            .iter()
+            .map(|track| FbxNodeTrack {
+                node_index: track.node_index,
+                target: track.target,
+                translation: slice_keyframes(&track.translation, start, end),
+                rotation: slice_keyframes(&track.rotation, start, end),
+                scale: slice_keyframes(&track.scale, start, end),
+            })
+            .collect(),
+        morph_tracks: animation
+            .morph_tracks
+            .iter()
+            .map(|track| slice_morph_track(track, start, end))
+            .collect(),
+        property_tracks: animation
+            .property_tracks
+            .iter()
+            .map(|track| FbxPropertyTrack {
+                node_index: track.node_index,
+                target: track.target,
+                property: track.property.clone(),
+                keyframes: slice_keyframes(&track.keyframes, start, end),
+            })
+            .collect(),
+        material_tracks: animation
+            .material_tracks
+            .iter()
+            .map(|track| FbxMaterialTrack {
+                material_index: track.material_index,
+                target: track.target,
+                property: track.property,
+                keyframes: slice_keyframes(&track.keyframes, start, end),
+            })
+            .collect(),
+        root_motion: animation
+            .root_motion
+            .as_ref()
+            .map(|root_motion| slice_root_motion(root_motion, start, end)),
+        markers: animation
+            .markers
+            .iter()
+            .filter(|marker| (start..=end).contains(&marker.time))
+            .map(|marker| FbxAnimationMarker {
+                time: marker.time - start,
+                ..marker.clone()
+            })
+            .collect(),
+    }
+}
+
+/// Cut the keys between `start` and `end` out of root motion, relative to
+/// the root at `start`.
+fn slice_root_motion(root_motion: &FbxRootMotion, start: f32, end: f32) -> FbxRootMotion {
+    let mut translation = slice_keyframes(&root_motion.translation, start, end);
+    if let Some(&origin) = translation.values.first() {
+        for value in &mut translation.values {
+            *value -= origin;
+        }
+    }
+
+    let mut rotation = slice_keyframes(&root_motion.rotation, start, end);
+    if let Some(&origin) = rotation.values.first() {
+        let inverse = origin.inverse();
+        for value in &mut rotation.values {
+            *value = inverse * *value;
+        }
+    }
+
+    FbxRootMotion {
+        node_index: root_motion.node_index,
+        duration: (end - start).max(0.0),
+        translation,
+        rotation,
+    }
+}
+
+/// Cut the keys between `start` and `end` out of a channel.
+///
+/// Keys added at the ends of cubic channels split the Hermite segment they
+/// fall in, taking the slope of the curve as tangents, so the cut channel
+/// follows the same curve.
+fn slice_keyframes<T>(keyframes: &FbxKeyframes<T>, start: f32, end: f32) -> FbxKeyframes<T>
+where
+    T: Animatable + Copy + Default + Add<Output = T> + Mul<f32, Output = T>,
+{
+    // Constant channels hold their value for any range
+    if keyframes.values.len() <= 1 {
+        return keyframes.clone();
+    }
+
+    let cubic = keyframes.interpolation == FbxInterpolation::Cubic;
+    let mut sliced = FbxKeyframes {
+        interpolation: keyframes.interpolation,
+        ..Default::default()
+    };
+    let mut push = |time: f32, value: T, tangents: (T, T)| {
+        sliced.times.push(time - start);
+        sliced.values.push(value);
+        if cubic {
+            sliced.tangents.push(tangents);
+        }
+    };
+
+    let boundary = |time: f32| {
+        if cubic {
+            sample_cubic_keyframes(keyframes, time).map(|(value, slope)| (value, (slope, slope)))
+        } else {
+            sample_keyframes(keyframes, time).map(|value| (value, Default::default()))
+        }
+    };
+
+    let first = keyframes.times.partition_point(|&t| t < start);
+    let last = keyframes.times.partition_point(|&t| t <= end);
+    if keyframes.times.get(first) != Some(&start)
+        && let Some((value, tangents)) = boundary(start)
+    {
+        push(start, value, tangents);
+    }
+    for index in first..last {
+        let tangents = keyframes.tangents.get(index).copied().unwrap_or_default();
+        push(keyframes.times[index], keyframes.values[index], tangents);
+    }
+    if (last == 0 || keyframes.times[last - 1] != end)
+        && (end > start || first < last)
+        && let Some((value, tangents)) = boundary(end)
+    {
+        push(end, value, tangents);
+    }
+
+    sliced
+}
+
+/// Cut the keys between `start` and `end` out of a morph weight track.
+fn slice_morph_track(track: &FbxMorphTrack, start: f32, end: f32) -> FbxMorphTrack {
+    let mut sliced = FbxMorphTrack {
+        node_index: track.node_index,
+        target: track.target,
+        times: Vec::new(),
+        weights: Vec::new(),
+    };
+    if track.times.is_empty() {
+        return sliced;
+    }
+
+    let count = track.weights.len() / track.times.len();
+    let sample = |time: f32| -> Vec<f32> {
+        let next = track.times.partition_point(|&t| t <= time);
+        let (a, b, t) = match next {
+            0 => (0, 0, 0.0),
+            n if n >= track.times.len() => (n - 1, n - 1, 0.0),
+            n => {
+                let (t0, t1) = (track.times[n - 1], track.times[n]);
+                (n - 1, n, (time - t0) / (t1 - t0))
+            }
+        };
+        (0..count)
+            .map(|i| {
+                let (wa, wb) = (track.weights[a * count + i], track.weights[b * count + i]);
+                wa + (wb - wa) * t
+            })
+            .collect()
+    };
+
+    let first = track.times.partition_point(|&t| t < start);
+    let last = track.times.partition_point(|&t| t <= end);
+    if track.times.get(first) != Some(&start) {
+        sliced.times.push(0.0);
+        sliced.weights.extend(sample(start));
+    }
+    for index in first..last {
+        sliced.times.push(track.times[index] - start);
+        sliced
+            .weights
+            .extend_from_slice(&track.weights[index * count..(index + 1) * count]);
+    }
+    if (last == 0 || track.times[last - 1] != end) && (end > start || first < last) {
+        sliced.times.push(end - start);
+        sliced.weights.extend(sample(end));
+    }
+
+    sliced
+}
+
+/// Add the clips of a layer stack to an animation graph.
+///
+/// Layers are ordered bottom to top. Each layer is combined with the result
+/// of all layers below it: override layers blend towards the layer by its
+/// weight, additive layers are added on top scaled by their weight.
+fn add_layers_to_graph(
+    graph: &mut AnimationGraph,
+    layers: &[(Handle<AnimationClip>, &ufbx::AnimLayer)],
+    weight: f32,
+    parent: AnimationNodeIndex,
+) {
+    let Some(((clip, layer), below)) = layers.split_last() else {
+        return;
+    };
+
+    if below.is_empty() {
+        graph.add_clip(clip.clone(), weight, parent);
+        return;
+    }
+
+    let layer_weight = layer.weight as f32;
+    if layer.additive {
+        let node = graph.add_additive_blend(weight, parent);
+        add_layers_to_graph(graph, below, 1.0, node);
+        graph.add_clip(clip.clone(), layer_weight, node);
+    } else {
+        let node = graph.add_blend(weight, parent);
+        add_layers_to_graph(graph, below, 1.0 - layer_weight, node);
+        graph.add_clip(clip.clone(), layer_weight, node);
+    }
+}
+
+/// Warn about layer settings that an animation graph cannot reproduce.
+fn check_layer_support(stack: &ufbx::AnimStack, layer: &ufbx::AnimLayer) {
+    if layer.weight_is_animated {
+        warn!(
+            "Animated weight of layer '{}' in stack '{}' is not supported, using its static weight",
+            layer.element.name, stack.element.name
+        );
+    }
+
+    // RotationAccumulationMode: 0 composes rotations per layer, 1 adds Euler
+    // angles per channel, which quaternion blending can only approximate.
+    let accumulation_mode = layer
+        .element
+        .props
+        .find_prop("RotationAccumulationMode")
+        .map_or(0, |prop| prop.value_int);
+    if layer.additive && accumulation_mode == 1 {
+        warn!(
+            "Per-channel rotation accumulation of layer '{}' in stack '{}' is approximated by composing rotations",
+            layer.element.name, stack.element.name
+        );
+    }
+}
+
+/// Convert absolute keyframes of an additive layer into offsets from the
+/// rest pose, matching how Bevy applies additive blends.
+fn make_additive(scene: &ufbx::Scene, animation: &mut FbxAnimation) {
+    for track in &mut animation.tracks {
+        let rest = convert_transform(&scene.nodes[track.node_index].local_transform);
+
+        for translation in &mut track.translation.values {
+            *translation -= rest.translation;
+        }
+        for rotation in &mut track.rotation.values {
+            *rotation *= rest.rotation.inverse();
+        }
+        for scale in &mut track.scale.values {
+            *scale -= rest.scale;
+        }
+    }
+
+    for track in &mut animation.morph_tracks {
+        let Some(mesh) = scene.nodes[track.node_index].mesh.as_ref() else {
+            continue;
+        };
+        let rest = morph_target_weights(mesh, |channel| channel.weight as f32);
+        if rest.is_empty() {
+            continue;
+        }
+        for (weight, rest) in track.weights.iter_mut().zip(rest.iter().cycle()) {
+            *weight -= rest;
+        }
+    }
+
+    for track in &mut animation.property_tracks {
+        let node = &scene.nodes[track.node_index];
+        let Some(rest) = rest_property_value(node, &track.property) else {
+            continue;
+        };
+        for value in &mut track.keyframes.values {
+            *value -= rest;
+        }
+    }
+
+    for track in &mut animation.material_tracks {
+        let rest = rest_material_value(&scene.materials[track.material_index], track.property);
+        for value in &mut track.keyframes.values {
+            *value -= rest;
+        }
+    }
+}
+
+/// Create an animation graph with every animation clip as a child of the root.
+pub fn create_animation_graph(
+    animations: &[Handle<AnimationClip>],
+    load_context: &mut LoadContext,
+) -> (Handle<AnimationGraph>, Vec<AnimationNodeIndex>) {
+    let (graph, graph_nodes) = AnimationGraph::from_clips(animations.iter().cloned());
+    let handle =
+        load_context.add_labeled_asset(FbxAssetLabel::AnimationGraph(0).to_string(), graph);
+    (handle, graph_nodes)
+}
+
+/// Bake the node transforms of an animation stack into keyframes.
+pub fn bake_anim_stack(
+    scene: &ufbx::Scene,
+    stack: &ufbx::AnimStack,
+    settings: &FbxAnimationSettings,
+) -> Result<FbxAnimation, FbxError> {
+    // Without layer baking only the base layer contributes to the clip
+    let layers = stack.layers.as_ref();
+    let (anim, authored_layer) = match layers {
+        [layer] => (&stack.anim, Some(layer)),
+        [base_layer, ..] if !settings.bake_layers => (&base_layer.anim, Some(base_layer)),
+        _ => (&stack.anim, None),
+    };
+
+    let mut animation = bake_anim(scene, anim, stack.element.name.as_ref(), settings)?;
+
+    // Blending several layers always needs baking
+    if let Some(layer) = authored_layer.filter(|_| settings.preserve_interpolation) {
+        apply_authored_keyframes(scene, stack.time_begin, layer, &mut animation);
+    }
+
+    Ok(animation)
+}
+
+/// Bake the node transforms of an animation into keyframes.
+pub fn bake_anim(
+    scene: &ufbx::Scene,
+    anim: &ufbx::Anim,
+    name: &str,
+    settings: &FbxAnimationSettings,
+) -> Result<FbxAnimation, FbxError> {
+    let baked = ufbx::bake_anim(
+        scene,
+        anim,
+        ufbx::BakeOpts {
+            trim_start_time: true,
+            resample_rate: settings.sample_rate,
+            key_reduction_enabled: settings.resample_tolerance > 0.0,
+            key_reduction_rotation: settings.resample_tolerance > 0.0,
+            key_reduction_threshold: settings.resample_tolerance,
+            ..Default::default()
+        },
+    )
+    .map_err(|e| {
+        FbxError::ConversionError(format!("Failed to bake animation '{}': {:?}", name, e))
+    })?;
+
+    let mut tracks = Vec::new();
+    for baked_node in baked.nodes.as_ref().iter() {
+        let node = &scene.nodes[baked_node.typed_id as usize];
+
+        // The root node only carries the axis and unit conversion
+        if node.is_root {
+            continue;
+        }
+
+        let mut rotation = FbxKeyframes {
+            times: baked_node
+                .rotation_keys
+                .iter()
+                .map(|k| k.time as f32)
+                .collect(),
+            values: baked_node
+                .rotation_keys
+                .iter()
+                .map(|k| convert_quat(&k.value))
+                .collect(),
+            ..Default::default()
+        };
+        make_rotations_continuous(&mut rotation.values);
+
+        tracks.push(FbxNodeTrack {
+            node_index: baked_node.typed_id as usize,
+            target: animation_target_id(node),
+            translation: FbxKeyframes {
+                times: baked_node
+                    .translation_keys
+                    .iter()
+                    .map(|k| k.time as f32)
+                    .collect(),
+                values: baked_node
+                    .translation_keys
+                    .iter()
+                    .map(|k| convert_vec3(&k.value))
+                    .collect(),
+                ..Default::default()
+            },
+            rotation,
+            scale: FbxKeyframes {
+                times: baked_node
+                    .scale_keys
+                    .iter()
+                    .map(|k| k.time as f32)
+                    .collect(),
+                values: baked_node
+                    .scale_keys
+                    .iter()
+                    .map(|k| convert_vec3(&k.value))
+                    .collect(),
+                ..Default::default()
+            },
+        });
+    }
+
+    let baked_elements: HashMap<u32, &ufbx::BakedElement> = baked
+        .elements
+        .as_ref()
+        .iter()
+        .map(|element| (element.element_id, element))
+        .collect();
+    let morph_tracks = scene
+        .nodes
+        .as_ref()
+        .iter()
+        .filter_map(|node| bake_morph_track(node, &baked_elements))
+        .collect();
+    let property_tracks = scene
+        .nodes
+        .as_ref()
+        .iter()
+        .flat_map(|node| bake_property_tracks(node, &baked_elements))
+        .collect();
+    let material_tracks = bake_material_tracks(scene, &baked_elements);
+
+    Ok(FbxAnimation {
+        name: name.to_string(),
+        duration: baked.playback_duration as f32,
+        tracks,
+        morph_tracks,
+        property_tracks,
+        material_tracks,
+        root_motion: None,
+        markers: Vec::new(),
+    })
+}
+
+/// Convert the baked `DeformPercent` curves of the blend channels of a node's
+/// mesh into morph target weights.
+fn bake_morph_track(
+    node: &ufbx::Node,
+    baked_elements: &HashMap<u32, &ufbx::BakedElement>,
+) -> Option<FbxMorphTrack> {
+    let mesh = node.mesh.as_ref()?;
+
+    let mut channel_keys: HashMap<u32, FbxKeyframes<f32>> = HashMap::new();
+    for deformer in mesh.blend_deformers.iter() {
+        for channel in deformer.channels.iter() {
+            let Some(prop) = baked_elements
+                .get(&channel.element.element_id)
+                .and_then(|element| {
+                    element
+                        .props
+                        .iter()
+                        .find(|prop| prop.name.as_ref() == "DeformPercent")
+                })
+            else {
+                continue;
+            };
+            channel_keys.insert(
+                channel.element.element_id,
+                FbxKeyframes {
+                    times: prop.keys.iter().map(|k| k.time as f32).collect(),
+                    values: prop.keys.iter().map(|k| k.value.x as f32 / 100.0).collect(),
+                    ..Default::default()
+                },
+            );
+        }
+    }
+    if channel_keys.is_empty() {
+        return None;
+    }
+
+    // Channels are keyed independently, so sample all of them at every key
+    let mut times: Vec<f32> = channel_keys
+        .values()
+        .flat_map(|keys| keys.times.iter().copied())
+        .collect();
+    times.sort_by(f32::total_cmp);
+    times.dedup();
+
+    let weights = times
+        .iter()
+        .flat_map(|&time| {
+            morph_target_weights(mesh, |channel| {
+                channel_keys
+                    .get(&channel.element.element_id)
+                    .and_then(|keys| sample_keyframes(keys, time))
+                    .unwrap_or(channel.weight as f32)
+            })
+        })
+        .collect();
+
+    Some(FbxMorphTrack {
+        node_index: node.element.typed_id as usize,
+        target: animation_target_id(node),
+        times,
+        weights,
+    })
+}
+
+/// Sample keyframes at `time`, holding the first and last values outside
+/// the keyed range.
+///
+/// Cubic keys follow their Hermite segments like the curves of the clips,
+/// see [`sample_cubic_keyframes`].
+pub fn sample_keyframes<T>(keyframes: &FbxKeyframes<T>, time: f32) -> Option<T>
+where
+    T: Animatable + Copy + Add<Output = T> + Mul<f32, Output = T>,
+{
+    if keyframes.interpolation == FbxInterpolation::Cubic {
+        return sample_cubic_keyframes(keyframes, time).map(|(value, _)| value);
+    }
+
+    let next = keyframes.times.partition_point(|&t| t <= time);
+    if next == 0 {
+        return keyframes.values.first().copied();
+    }
+    if next >= keyframes.values.len() {
+        return keyframes.values.last().copied();
+    }
+
+    let (start, end) = (keyframes.times[next - 1], keyframes.times[next]);
+    let (a, b) = (keyframes.values[next - 1], keyframes.values[next]);
+    match keyframes.interpolation {
+        FbxInterpolation::Constant => Some(a),
+        _ if end <= start => Some(b),
+        _ => Some(T::interpolate(&a, &b, (time - start) / (end - start))),
+    }
+}
+
+/// Sample a cubic channel at `time`, along with the slope of the curve there
+/// in units per second.
+///
+/// Segments are Hermite splines through the key tangents, as evaluated by
+/// Bevy's `CubicKeyframeCurve`. The first and last values are held with a
+/// flat slope outside the keyed range.
+pub fn sample_cubic_keyframes<T>(keyframes: &FbxKeyframes<T>, time: f32) -> Option<(T, T)>
+where
+    T: Copy + Add<Output = T> + Mul<f32, Output = T>,
+{
+    let flat = |value: T| (value, value * 0.0);
+    let next = keyframes.times.partition_point(|&t| t <= time);
+    if next == 0 {
+        return keyframes.values.first().copied().map(flat);
+    }
+    if next >= keyframes.values.len() {
+        return keyframes.values.last().copied().map(flat);
+    }
+
+    let (start, end) = (keyframes.times[next - 1], keyframes.times[next]);
+    let (a, b) = (keyframes.values[next - 1], keyframes.values[next]);
+    if end <= start {
+        return Some(flat(b));
+    }
+    let out_tangent = keyframes
+        .tangents
+        .get(next - 1)
+        .map_or(a * 0.0, |tangents| tangents.1);
+    let in_tangent = keyframes
+        .tangents
+        .get(next)
+        .map_or(b * 0.0, |tangents| tangents.0);
+
+    let step = end - start;
+    let s = (time - start) / step;
+    let (s2, s3) = (s * s, s * s * s);
+    let value = a * (2.0 * s3 - 3.0 * s2 + 1.0)
+        + out_tangent * (step * (s3 - 2.0 * s2 + s))
+        + b * (3.0 * s2 - 2.0 * s3)
+        + in_tangent * (step * (s3 - s2));
+    let slope = a * ((6.0 * s2 - 6.0 * s) / step)
+        + out_tangent * (3.0 * s2 - 4.0 * s + 1.0)
+        + b * ((6.0 * s - 6.0 * s2) / step)
+        + in_tangent * (3.0 * s2 - 2.0 * s);
+    Some((value, slope))
+}
+
+/// Replace baked channels of a single layer with its authored keyframes
+/// where Bevy curves can represent them, keeping stepped and cubic keys.
+fn apply_authored_keyframes(
+    scene: &ufbx::Scene,
+    time_begin: f64,
+    layer: &ufbx::AnimLayer,
+    animation: &mut FbxAnimation,
+) {
+    for track in &mut animation.tracks {
+        let node = &scene.nodes[track.node_index];
+
+        if let Some(keyframes) =
+            authored_vec3_keyframes(time_begin, layer, node, "Lcl Translation", |transform| {
+                transform.translation
+            })
+        {
+            track.translation = keyframes;
+        }
+        if let Some(keyframes) = authored_rotation_keyframes(time_begin, layer, node) {
+            track.rotation = keyframes;
+        }
+        if let Some(keyframes) =
+            authored_vec3_keyframes(time_begin, layer, node, "Lcl Scaling", |transform| {
+                transform.scale
+            })
+        {
+            track.scale = keyframes;
+        }
+    }
+}
+
+/// Component curves of an animated property whose keys share a single
+/// interpolation mode.
+struct AuthoredCurves<'a> {
+    interpolation: FbxInterpolation,
+    default_value: ufbx::Vec3,
+    curves: [Option<&'a ufbx::AnimCurve>; 3],
+}
+
+/// Find the curves animating a node property, if every key uses the same
+/// interpolation and extrapolation holds the end values.
+fn authored_curves<'a>(
+    layer: &'a ufbx::AnimLayer,
+    node: &ufbx::Node,
+    prop_name: &str,
+) -> Option<AuthoredCurves<'a>> {
+    let anim_prop = layer.anim_props.iter().find(|anim_prop| {
+        anim_prop.element.element_id == node.element.element_id
+            && anim_prop.prop_name.as_ref() == prop_name
+    })?;
+    let anim_value = &anim_prop.anim_value;
+    let curves = [0, 1, 2].map(|axis| anim_value.curves[axis].as_deref());
+
+    let mut interpolation = None;
+    for curve in curves.iter().flatten() {
+        if curve.keyframes.is_empty()
+            || curve.pre_extrapolation.mode != ufbx::ExtrapolationMode::Constant
+            || curve.post_extrapolation.mode != ufbx::ExtrapolationMode::Constant
+        {
+            return None;
+        }
+
+        // The interpolation of the last key never applies
+        let keys = curve.keyframes.as_ref();
+        for key in &keys[..keys.len().saturating_sub(1)] {
+            let key_interpolation = convert_interpolation(key.interpolation)?;
+            if *interpolation.get_or_insert(key_interpolation) != key_interpolation {
+                return None;
+            }
+        }
+    }
+
+    Some(AuthoredCurves {
+        interpolation: interpolation.unwrap_or(FbxInterpolation::Constant),
+        default_value: anim_value.default_value,
+        curves,
+    })
+}
+
+/// Authored keyframes of a translation or scale property.
+fn authored_vec3_keyframes(
+    time_begin: f64,
+    layer: &ufbx::AnimLayer,
+    node: &ufbx::Node,
+    prop_name: &str,
+    sample: fn(&Transform) -> Vec3,
+) -> Option<FbxKeyframes<Vec3>> {
+    let curves = authored_curves(layer, node, prop_name)?;
+    if curves.interpolation == FbxInterpolation::Constant {
+        return Some(stepped_keyframes(time_begin, layer, node, &curves, sample));
+    }
+
+    // Linear and cubic keys are copied directly, which requires the property
+    // to map straight onto the node transform and all components to be keyed
+    // at the same times.
+    if has_pivots(node) {
+        return None;
+    }
+    let first = curves.curves.iter().flatten().next()?;
+    let same_times = curves.curves.iter().flatten().all(|curve| {
+        curve.keyframes.len() == first.keyframes.len()
+            && curve
+                .keyframes
+                .iter()
+                .zip(first.keyframes.iter())
+                .all(|(a, b)| a.time == b.time)
+    });
+    if !same_times {
+        return None;
+    }
+    if curves.interpolation == FbxInterpolation::Cubic
+        && !curves
+            .curves
+            .iter()
+            .flatten()
+            .all(|curve| has_hermite_tangents(curve))
+    {
+        return None;
+    }
+
+    let default_value = [
+        curves.default_value.x,
+        curves.default_value.y,
+        curves.default_value.z,
+    ];
+    let mut keyframes = FbxKeyframes {
+        interpolation: curves.interpolation,
+        ..Default::default()
+    };
+    for (index, key) in first.keyframes.iter().enumerate() {
+        let keys = curves
+            .curves
+            .map(|curve| curve.map(|curve| &curve.keyframes[index]));
+        keyframes.times.push((key.time - time_begin) as f32);
+        keyframes
+            .values
+            .push(vec3_from_keys(&keys, default_value, |key| key.value));
+        if curves.interpolation == FbxInterpolation::Cubic {
+            keyframes.tangents.push((
+                vec3_from_keys(&keys, [0.0; 3], |key| tangent_slope(&key.left)),
+                vec3_from_keys(&keys, [0.0; 3], |key| tangent_slope(&key.right)),
+            ));
+        }
+    }
+
+    Some(keyframes)
+}
+
+/// Authored keyframes of a rotation property.
+///
+/// Interpolated Euler angles do not match quaternion interpolation, so only
+/// stepped rotations are kept.
+fn authored_rotation_keyframes(
+    time_begin: f64,
+    layer: &ufbx::AnimLayer,
+    node: &ufbx::Node,
+) -> Option<FbxKeyframes<Quat>> {
+    let curves = authored_curves(layer, node, "Lcl Rotation")?;
+    if curves.interpolation != FbxInterpolation::Constant {
+        return None;
+    }
+
+    let mut keyframes = stepped_keyframes(time_begin, layer, node, &curves, |transform| {
+        transform.rotation
+    });
+    make_rotations_continuous(&mut keyframes.values);
+    Some(keyframes)
+}
+
+/// Keyframes of a stepped property, evaluated by ufbx at every key time so
+/// pivots and rotation order are applied.
+fn stepped_keyframes<T: Default>(
+    time_begin: f64,
+    layer: &ufbx::AnimLayer,
+    node: &ufbx::Node,
+    curves: &AuthoredCurves,
+    sample: impl Fn(&Transform) -> T,
+) -> FbxKeyframes<T> {
+    let mut key_times: Vec<f64> = curves
+        .curves
+        .iter()
+        .flatten()
+        .flat_map(|curve| curve.keyframes.iter().map(|key| key.time))
+        .collect();
+    key_times.sort_by(f64::total_cmp);
+    key_times.dedup();
+
+    let mut keyframes = FbxKeyframes {
+        interpolation: FbxInterpolation::Constant,
+        ..Default::default()
+    };
+    for time in key_times {
+        let transform = convert_transform(&ufbx::evaluate_transform(&layer.anim, node, time));
+        keyframes.times.push((time - time_begin) as f32);
+        keyframes.values.push(sample(&transform));
+    }
+    keyframes
+}
+
+/// Whether pivots or offsets make the node translation differ from its
+/// `Lcl Translation` property.
+fn has_pivots(node: &ufbx::Node) -> bool {
+    [
+        "RotationOffset",
+        "RotationPivot",
+        "ScalingOffset",
+        "ScalingPivot",
+    ]
+    .iter()
+    .any(|name| {
+        node.element.props.find_prop(name).is_some_and(|prop| {
+            prop.value_vec4.x != 0.0 || prop.value_vec4.y != 0.0 || prop.value_vec4.z != 0.0
+        })
+    })
+}
+
+/// Whether the Bezier handles of a cubic curve sit at a third of each
+/// segment, which makes them equivalent to Hermite tangents.
+fn has_hermite_tangents(curve: &ufbx::AnimCurve) -> bool {
+    curve.keyframes.windows(2).all(|pair| {
+        let third = (pair[1].time - pair[0].time) / 3.0;
+        let tolerance = third * TANGENT_TOLERANCE;
+        (f64::from(pair[0].right.dx) - third).abs() <= tolerance
+            && (f64::from(pair[1].left.dx) - third).abs() <= tolerance
+    })
+}
+
+/// Slope of a keyframe tangent in units per second.
+fn tangent_slope(tangent: &ufbx::Tangent) -> f64 {
+    if tangent.dx > 0.0 {
+        f64::from(tangent.dy / tangent.dx)
+    } else {
+        0.0
+    }
+}
+
+/// Combine per-component keys into a vector, using defaults for
+/// components without a curve.
+fn vec3_from_keys(
+    keys: &[Option<&ufbx::Keyframe>; 3],
+    default_value: [f64; 3],
+    value: impl Fn(&ufbx::Keyframe) -> f64,
+) -> Vec3 {
+    let component = |axis: usize| keys[axis].map_or(default_value[axis], &value) as f32;
+    Vec3::new(component(0), component(1), component(2))
+}
+
+/// Flip quaternion signs so consecutive keys lie in the same hemisphere.
+///
+/// `q` and `-q` describe the same rotation, but interpolating between keys
+/// of opposite sign takes the long way around.
+pub fn make_rotations_continuous(rotations: &mut [Quat]) {
+    for i in 1..rotations.len() {
+        if rotations[i - 1].dot(rotations[i]) < 0.0 {
+            rotations[i] = -rotations[i];
+        }
+    }
+}
+
+/// Create a Bevy animation clip from converted FBX animation data.
+pub fn create_animation_clip(animation: &FbxAnimation) -> AnimationClip {
+    let mut clip = AnimationClip::default();
+
+    for track in &animation.tracks {
+        let curves = [
+            vec3_curve(animated_field!(Transform::translation), &track.translation),
+            quat_curve(animated_field!(Transform::rotation), &track.rotation),
+            vec3_curve(animated_field!(Transform::scale), &track.scale),
+        ];
+        for curve in curves.into_iter().flatten() {
+            clip.add_variable_curve_to_target(track.target, curve);
+        }
+    }
+
+    for track in &animation.morph_tracks {
+        if let Some(curve) = weights_curve(track) {
+            clip.add_variable_curve_to_target(track.target, curve);
+        }
+    }
+
+    for track in &animation.property_tracks {
+        if let Some(curve) = property_curve(track) {
+            clip.add_variable_curve_to_target(track.target, curve);
+        }
+    }
+
+    for track in &animation.material_tracks {
+        if let Some(curve) = material_curve(track) {
+            clip.add_variable_curve_to_target(track.target, curve);
+        }
+    }
+
+    for marker in &animation.markers {
+        clip.add_event(
+            marker.time,
+            FbxAnimationEvent {
+                name: marker.name.clone(),
+                payload: marker.payload.clone(),
+            },
+        );
+    }
+
+    clip.set_duration(animation.duration);
+    clip
+}
+
+/// Create a curve animating a `Vec3` property from keyframes.
+fn vec3_curve(
+    property: impl AnimatableProperty<Property = Vec3> + Clone,
+    keyframes: &FbxKeyframes<Vec3>,
+) -> Option<VariableCurve> {
+    let samples = keyframes
+        .times
+        .iter()
+        .copied()
+        .zip(keyframes.values.iter().copied());
+
+    match (keyframes.values.len(), keyframes.interpolation) {
+        (0, _) => None,
+        (1, _) => Some(VariableCurve::new(AnimatableCurve::new(
+            property,
+            ConstantCurve::new(Interval::EVERYWHERE, keyframes.values[0]),
+        ))),
+        (_, FbxInterpolation::Constant) => SteppedKeyframeCurve::new(samples)
+            .ok()
+            .map(|curve| VariableCurve::new(AnimatableCurve::new(property, curve))),
+        (_, FbxInterpolation::Linear) => UnevenSampleAutoCurve::new(samples)
+            .ok()
+            .map(|curve| VariableCurve::new(AnimatableCurve::new(property, curve))),
+        (_, FbxInterpolation::Cubic) => CubicKeyframeCurve::new(
+            keyframes.times.iter().copied(),
+            keyframes
+                .values
+                .iter()
+                .zip(keyframes.tangents.iter())
+                .flat_map(|(value, (tangent_in, tangent_out))| [*tangent_in, *value, *tangent_out]),
+        )
+        .ok()
+        .map(|curve| VariableCurve::new(AnimatableCurve::new(property, curve))),
+    }
+}
+
+/// Create a curve animating the morph weights of a node.
+fn weights_curve(track: &FbxMorphTrack) -> Option<VariableCurve> {
+    match track.times.len() {
+        0 => None,
+        1 => Some(VariableCurve::new(WeightsCurve(ConstantCurve::new(
+            Interval::EVERYWHERE,
+            track.weights.clone(),
+        )))),
+        _ => {
+            WideLinearKeyframeCurve::new(track.times.iter().copied(), track.weights.iter().copied())
+                .ok()
+                .map(|curve| VariableCurve::new(WeightsCurve(curve)))
+        }
+    }
+}
+
+/// Create a curve animating a `Quat` property from keyframes.
+///
+/// Rotations are never cubic, so anything but stepped keys is interpolated
+/// linearly.
+fn quat_curve(
+    property: impl AnimatableProperty<Property = Quat> + Clone,
+    keyframes: &FbxKeyframes<Quat>,
+) -> Option<VariableCurve> {
+    let samples = keyframes
+        .times
+        .iter()
+        .copied()
+        .zip(keyframes.values.iter().copied());
+
+    match (keyframes.values.len(), keyframes.interpolation) {
+        (0, _) => None,
+        (1, _) => Some(VariableCurve::new(AnimatableCurve::new(
+            property,
+            ConstantCurve::new(Interval::EVERYWHERE, keyframes.values[0]),
+        ))),
+        (_, FbxInterpolation::Constant) => SteppedKeyframeCurve::new(samples)
+            .ok()
+            .map(|curve| VariableCurve::new(AnimatableCurve::new(property, curve))),
+        _ => UnevenSampleAutoCurve::new(samples)
+            .ok()
+            .map(|curve| VariableCurve::new(AnimatableCurve::new(property, curve))),
+    }
+}
@@ -0,0 +1,537 @@
+//! Aim, parent, position, rotation and scale constraints of FBX nodes.
+
+use crate::animation::make_rotations_continuous;
+use crate::error::FbxError;
+use crate::node::{animation_target_id, node_name};
+use crate::types::{FbxAnimation, FbxKeyframes, FbxNodeTrack};
+use crate::utils::{convert_matrix, convert_quat, convert_transform, convert_vec3};
+use bevy::ecs::entity::{EntityMapper, MapEntities};
+use bevy::prelude::*;
+use bevy::transform::helper::TransformHelper;
+
+/// How a constraint drives its node.
+#[derive(Reflect, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FbxConstraintKind {
+    /// Points the aim vector of the node at the targets
+    Aim,
+    /// Moves and rotates the node as if parented to the targets
+    Parent,
+    /// Moves the node to the targets
+    Position,
+    /// Rotates the node like the targets
+    Rotation,
+    /// Scales the node like the targets
+    Scale,
+}
+
+/// How the up vector of an aim constraint is chosen.
+#[derive(Reflect, Debug, Clone, Copy, PartialEq)]
+pub enum FbxAimUp {
+    /// Only the aim vector is aligned
+    None,
+    /// Up points along a world space vector, in the axes of the scene
+    Vector(Vec3),
+    /// Up points at the up node
+    ToNode,
+    /// Up follows the up vector of the up node
+    AlignNode,
+}
+
+/// A target of a constraint, with its offset from the target.
+#[derive(Reflect, Debug, Clone, Copy, PartialEq)]
+pub struct FbxConstraintTarget {
+    pub entity: Entity,
+    pub weight: f32,
+    /// Transform of the node relative to this target, used by parent
+    /// constraints
+    pub offset: Transform,
+}
+
+/// FBX constraint on a node entity, evaluated after animation by
+/// [`evaluate_fbx_constraints`].
+#[derive(Component, Reflect, Debug, Clone, PartialEq)]
+#[component(map_entities)]
+#[reflect(Component)]
+pub struct FbxConstraint {
+    pub kind: FbxConstraintKind,
+    /// Blend between the animated and the constrained transform
+    pub weight: f32,
+    pub targets: Vec<FbxConstraintTarget>,
+    /// Constrained translation axes, in the space of the parent
+    pub translation_axes: [bool; 3],
+    /// Constrained rotation axes, as XYZ Euler angles in the space of the parent
+    pub rotation_axes: [bool; 3],
+    /// Constrained scale axes
+    pub scale_axes: [bool; 3],
+    /// Offset applied on top of the targets, except for parent constraints
+    pub offset: Transform,
+    /// Local axis of the node pointed at the targets by aim constraints
+    pub aim_vector: Vec3,
+    /// Local axis of the node aligned with the up direction by aim constraints
+    pub up_vector: Vec3,
+    pub aim_up: FbxAimUp,
+    pub up_node: Option<Entity>,
+}
+
+impl MapEntities for FbxConstraint {
+    fn map_entities<E: EntityMapper>(&mut self, entity_mapper: &mut E) {
+        for target in &mut self.targets {
+            target.entity = entity_mapper.get_mapped(target.entity);
+        }
+        if let Some(up_node) = &mut self.up_node {
+            *up_node = entity_mapper.get_mapped(*up_node);
+        }
+    }
+}
+
+impl FbxConstraint {
+    /// Convert a ufbx constraint, with `entity` giving the entity of each
+    /// node.
+    ///
+    /// `root_rotation` converts the file axes to the axes of the scene, and
+    /// is applied to the world up vector. Returns `None` for inactive
+    /// constraints and for IK and other unsupported constraint types.
+    pub fn from_ufbx(
+        constraint: &ufbx::Constraint,
+        root_rotation: Quat,
+        entity: impl Fn(&ufbx::Node) -> Entity,
+    ) -> Option<Self> {
+        let kind = match constraint.type_ {
+            ufbx::ConstraintType::Aim => FbxConstraintKind::Aim,
+            ufbx::ConstraintType::Parent => FbxConstraintKind::Parent,
+            ufbx::ConstraintType::Position => FbxConstraintKind::Position,
+            ufbx::ConstraintType::Rotation => FbxConstraintKind::Rotation,
+            ufbx::ConstraintType::Scale => FbxConstraintKind::Scale,
+            _ => {
+                warn!(
+                    "Constraint '{}' of type '{}' is not supported",
+                    constraint.element.name, constraint.type_name
+                );
+                return None;
+            }
+        };
+        if !constraint.active {
+            return None;
+        }
+
+        let world_up =
+            constraint
+                .element
+                .props
+                .find_prop("WorldUpVector")
+                .map_or(Vec3::Y, |prop| {
+                    Vec3::new(
+                        prop.value_vec4.x as f32,
+                        prop.value_vec4.y as f32,
+                        prop.value_vec4.z as f32,
+                    )
+                });
+        let aim_up = match constraint.aim_up_type {
+            ufbx::ConstraintAimUpType::Scene => FbxAimUp::Vector(Vec3::Y),
+            ufbx::ConstraintAimUpType::Vector => FbxAimUp::Vector(root_rotation * world_up),
+            ufbx::ConstraintAimUpType::ToNode => FbxAimUp::ToNode,
+            ufbx::ConstraintAimUpType::AlignNode => FbxAimUp::AlignNode,
+            _ => FbxAimUp::None,
+        };
+
+        // Camera and light nodes are rotated to look down -Z, which moves
+        // their local axes
+        let node_rotation = constraint.node.as_ref().map_or(Quat::IDENTITY, |node| {
+            convert_quat(&node.adjust_post_rotation).inverse()
+        });
+
+        Some(Self {
+            kind,
+            weight: constraint.weight as f32,
+            targets: constraint
+                .targets
+                .iter()
+                .map(|target| FbxConstraintTarget {
+                    entity: entity(&target.node),
+                    weight: target.weight as f32,
+                    offset: convert_transform(&target.transform),
+                })
+                .collect(),
+            translation_axes: constraint.constrain_translation,
+            rotation_axes: constraint.constrain_rotation,
+            scale_axes: constraint.constrain_scale,
+            offset: convert_transform(&constraint.transform_offset),
+            aim_vector: node_rotation * convert_vec3(&constraint.aim_vector),
+            up_vector: node_rotation * convert_vec3(&constraint.aim_up_vector),
+            aim_up,
+            up_node: constraint.aim_up_node.as_ref().map(|node| entity(node)),
+        })
+    }
+
+    /// Constrain the local transform of a node.
+    ///
+    /// `parent` is the world transform of the parent of the node, `targets`
+    /// the world transforms of the targets in order and `up_node` the world
+    /// transform of the up node of aim constraints.
+    pub fn apply(
+        &self,
+        local: &Transform,
+        parent: &Transform,
+        targets: &[Transform],
+        up_node: Option<&Transform>,
+    ) -> Transform {
+        let current = parent.mul_transform(*local);
+        let Some(world) = self.solve(&current, targets, up_node) else {
+            return *local;
+        };
+        let solved = Transform::from_matrix(parent.to_matrix().inverse() * world.to_matrix());
+
+        let constrained = Transform {
+            translation: Vec3::select(
+                BVec3::from_array(self.translation_axes),
+                solved.translation,
+                local.translation,
+            ),
+            rotation: select_rotation(self.rotation_axes, solved.rotation, local.rotation),
+            scale: Vec3::select(
+                BVec3::from_array(self.scale_axes),
+                solved.scale,
+                local.scale,
+            ),
+        };
+        let weight = self.weight.clamp(0.0, 1.0);
+        Transform {
+            translation: local.translation.lerp(constrained.translation, weight),
+            rotation: local.rotation.slerp(constrained.rotation, weight),
+            scale: local.scale.lerp(constrained.scale, weight),
+        }
+    }
+
+    /// Constrained world transform of a node, or `None` without weighted
+    /// targets.
+    fn solve(
+        &self,
+        current: &Transform,
+        targets: &[Transform],
+        up_node: Option<&Transform>,
+    ) -> Option<Transform> {
+        let weights: Vec<f32> = self
+            .targets
+            .iter()
+            .take(targets.len())
+            .map(|target| target.weight.max(0.0))
+            .collect();
+        let targets = &targets[..weights.len()];
+        let total: f32 = weights.iter().sum();
+        if total <= f32::EPSILON {
+            return None;
+        }
+        let average = |value: &dyn Fn(usize, &Transform) -> Vec3| {
+            targets
+                .iter()
+                .enumerate()
+                .map(|(index, target)| value(index, target) * weights[index])
+                .sum::<Vec3>()
+                / total
+        };
+
+        let mut world = *current;
+        match self.kind {
+            FbxConstraintKind::Position => {
+                world.translation =
+                    average(&|_, target| target.translation) + self.offset.translation;
+            }
+            FbxConstraintKind::Rotation => {
+                let rotations: Vec<Quat> = targets.iter().map(|target| target.rotation).collect();
+                world.rotation = average_rotation(&rotations, &weights) * self.offset.rotation;
+            }
+            FbxConstraintKind::Scale => {
+                world.scale = average(&|_, target| target.scale) * self.offset.scale;
+            }
+            FbxConstraintKind::Parent => {
+                let offset_targets: Vec<Transform> = targets
+                    .iter()
+                    .zip(&self.targets)
+                    .map(|(target, constraint_target)| {
+                        target.mul_transform(constraint_target.offset)
+                    })
+                    .collect();
+                world.translation = average(&|index, _| offset_targets[index].translation);
+                let rotations: Vec<Quat> = offset_targets
+                    .iter()
+                    .map(|target| target.rotation)
+                    .collect();
+                world.rotation = average_rotation(&rotations, &weights);
+            }
+            FbxConstraintKind::Aim => {
+                let direction = (average(&|_, target| target.translation) - current.translation)
+                    .normalize_or_zero();
+                if direction == Vec3::ZERO {
+                    return None;
+                }
+                let up = match self.aim_up {
+                    FbxAimUp::None => None,
+                    FbxAimUp::Vector(up) => Some(up),
+                    FbxAimUp::ToNode => up_node.map(|node| node.translation - current.translation),
+                    FbxAimUp::AlignNode => up_node.map(|node| node.rotation * self.up_vector),
+                };
+                world.rotation = aim_rotation(self.aim_vector, self.up_vector, direction, up)
+                    * self.offset.rotation;
+            }
+        }
+        Some(world)
+    }
+}
+
+/// Rotation pointing the local `aim` axis along `direction`, twisted so the
+/// local `up` axis leans towards `world_up`.
+pub fn aim_rotation(aim: Vec3, up: Vec3, direction: Vec3, world_up: Option<Vec3>) -> Quat {
+    let aim = aim.try_normalize().unwrap_or(Vec3::X);
+    let rotation = Quat::from_rotation_arc(aim, direction);
+    let Some(world_up) = world_up else {
+        return rotation;
+    };
+
+    // Project both up vectors onto the plane perpendicular to the aim
+    let project = |v: Vec3| (v - direction * v.dot(direction)).normalize_or_zero();
+    let current_up = project(rotation * up);
+    let wanted_up = project(world_up);
+    if current_up == Vec3::ZERO || wanted_up == Vec3::ZERO {
+        return rotation;
+    }
+    let angle = current_up
+        .cross(wanted_up)
+        .dot(direction)
+        .atan2(current_up.dot(wanted_up));
+    Quat::from_axis_angle(direction, angle) * rotation
+}
+
+/// Weighted average of rotations, flipped into the hemisphere of the first.
+fn average_rotation(rotations: &[Quat], weights: &[f32]) -> Quat {
+    let Some(&first) = rotations.first() else {
+        return Quat::IDENTITY;
+    };
+    let sum = rotations
+        .iter()
+        .zip(weights)
+        .fold(Vec4::ZERO, |sum, (&rotation, &weight)| {
+            let rotation = if first.dot(rotation) < 0.0 {
+                -rotation
+            } else {
+                rotation
+            };
+            sum + Vec4::from(rotation) * weight
+        });
+    if sum.length_squared() <= f32::EPSILON {
+        return first;
+    }
+    Quat::from_vec4(sum).normalize()
+}
+
+/// Take the constrained Euler angles of `solved` and the others of `local`.
+fn select_rotation(axes: [bool; 3], solved: Quat, local: Quat) -> Quat {
+    match axes {
+        [true, true, true] => solved,
+        [false, false, false] => local,
+        _ => {
+            let solved = Vec3::from(solved.to_euler(EulerRot::XYZ));
+            let local = Vec3::from(local.to_euler(EulerRot::XYZ));
+            let angles = Vec3::select(BVec3::from_array(axes), solved, local);
+            Quat::from_euler(EulerRot::XYZ, angles.x, angles.y, angles.z)
+        }
+    }
+}
+
+/// Constrain the transforms of nodes after animation has been applied.
+///
+/// Targets are read with their animated transforms of the current frame.
+/// Constraints whose targets are themselves constrained see the targets
+/// before their constraints are applied.
+pub fn evaluate_fbx_constraints(
+    constraints: Query<(Entity, &FbxConstraint, Option<&ChildOf>)>,
+    mut transforms: ParamSet<(TransformHelper, Query<&mut Transform>)>,
+) {
+    let mut constrained = Vec::new();
+    for (entity, constraint, child_of) in &constraints {
+        let Ok(local) = transforms.p1().get(entity).copied() else {
+            continue;
+        };
+        let helper = transforms.p0();
+        let world = |entity: Entity| {
+            helper
+                .compute_global_transform(entity)
+                .ok()
+                .map(|global| global.compute_transform())
+        };
+
+        let parent = match child_of {
+            Some(child_of) => world(child_of.parent()),
+            None => Some(Transform::IDENTITY),
+        };
+        let targets: Option<Vec<Transform>> = constraint
+            .targets
+            .iter()
+            .map(|target| world(target.entity))
+            .collect();
+        let (Some(parent), Some(targets)) = (parent, targets) else {
+            continue;
+        };
+        let up_node = constraint.up_node.and_then(world);
+        constrained.push((
+            entity,
+            constraint.apply(&local, &parent, &targets, up_node.as_ref()),
+        ));
+    }
+
+    let mut transforms = transforms.p1();
+    for (entity, transform) in constrained {
+        if let Ok(mut current) = transforms.get_mut(entity) {
+            current.set_if_neq(transform);
+        }
+    }
+}
+
+/// Constraints of a scene that are evaluated, with their constrained node.
+fn evaluated_constraints(
+    scene: &ufbx::Scene,
+) -> Vec<(&ufbx::Constraint, &ufbx::Node, FbxConstraint)> {
+    node_constraints(scene, |_| Entity::PLACEHOLDER)
+}
+
+/// Supported constraints of a scene with their constrained node, targeting
+/// the entities given by `entity`.
+///
+/// Only the first constraint of each node is kept, both when baking and when
+/// evaluating constraints at runtime.
+pub fn node_constraints(
+    scene: &ufbx::Scene,
+    entity: impl Fn(&ufbx::Node) -> Entity,
+) -> Vec<(&ufbx::Constraint, &ufbx::Node, FbxConstraint)> {
+    let root_rotation = convert_quat(&scene.metadata.root_rotation);
+    let mut constraints: Vec<(&ufbx::Constraint, &ufbx::Node, FbxConstraint)> = Vec::new();
+    for constraint in scene.constraints.as_ref().iter() {
+        let Some(node) = constraint.node.as_deref() else {
+            continue;
+        };
+        let Some(converted) = FbxConstraint::from_ufbx(constraint, root_rotation, &entity) else {
+            continue;
+        };
+        if constraints
+            .iter()
+            .any(|(_, constrained, _)| constrained.element.element_id == node.element.element_id)
+        {
+            warn!(
+                "Node '{}' has several constraints, only the first one is evaluated",
+                node_name(node)
+            );
+            continue;
+        }
+        constraints.push((&**constraint, node, converted));
+    }
+    constraints
+}
+
+/// Bake the constraints of a scene into the node tracks of an animation of a
+/// stack, or of one of its layers.
+///
+/// The whole stack is evaluated at the sample rate and every constrained node
+/// gets a track with its constrained transform, replacing its animated one.
+pub fn bake_constraints(
+    scene: &ufbx::Scene,
+    stack: &ufbx::AnimStack,
+    animation: &mut FbxAnimation,
+    sample_rate: f64,
+) -> Result<(), FbxError> {
+    let constraints = evaluated_constraints(scene);
+    if constraints.is_empty() {
+        return Ok(());
+    }
+
+    let frames = (animation.duration as f64 * sample_rate).ceil().max(0.0) as usize;
+    let times: Vec<f32> = (0..=frames)
+        .map(|frame| (frame as f64 / sample_rate).min(animation.duration as f64) as f32)
+        .collect();
+
+    let mut values = vec![Vec::with_capacity(times.len()); constraints.len()];
+    for &time in &times {
+        let evaluated = ufbx::evaluate_scene(
+            scene,
+            &stack.anim,
+            stack.time_begin + time as f64,
+            Default::default(),
+        )
+        .map_err(|e| {
+            FbxError::ConversionError(format!(
+                "Failed to evaluate constraints of '{}': {:?}",
+                stack.element.name, e
+            ))
+        })?;
+        let world = |node: &ufbx::Node| {
+            Transform::from_matrix(convert_matrix(
+                &evaluated.nodes[node.element.typed_id as usize].node_to_world,
+            ))
+        };
+
+        for ((constraint, node, converted), values) in constraints.iter().zip(&mut values) {
+            let evaluated_node = &evaluated.nodes[node.element.typed_id as usize];
+            let local = convert_transform(&evaluated_node.local_transform);
+            let parent = evaluated_node
+                .parent
+                .as_ref()
+                .map_or(Transform::IDENTITY, |parent| world(parent));
+            let targets: Vec<Transform> = constraint
+                .targets
+                .iter()
+                .map(|target| world(&target.node))
+                .collect();
+            let up_node = constraint.aim_up_node.as_ref().map(|node| world(node));
+            values.push(converted.apply(&local, &parent, &targets, up_node.as_ref()));
+        }
+    }
+
+    for ((_, node, _), values) in constraints.iter().zip(values) {
+        let node_index = node.element.typed_id as usize;
+        let mut rotation: Vec<Quat> = values.iter().map(|value| value.rotation).collect();
+        make_rotations_continuous(&mut rotation);
+        let track = FbxNodeTrack {
+            node_index,
+            target: animation_target_id(node),
+            translation: FbxKeyframes {
+                times: times.clone(),
+                values: values.iter().map(|value| value.translation).collect(),
+                ..Default::default()
+            },
+            rotation: FbxKeyframes {
+                times: times.clone(),
+                values: rotation,
+                ..Default::default()
+            },
+            scale: FbxKeyframes {
+                times: times.clone(),
+                values: values.iter().map(|value| value.scale).collect(),
+                ..Default::default()
+            },
+        };
+
+        match animation
+            .tracks
+            .iter_mut()
+            .find(|track| track.node_index == node_index)
+        {
+            Some(existing) => *existing = track,
+            None => animation.tracks.push(track),
+        }
+        debug!("Baked constraint of node '{}'", node_name(node));
+    }
+
+    Ok(())
+}
+
+/// Remove the tracks of constrained nodes from an animation.
+///
+/// Additive layers would otherwise offset the constrained transforms baked
+/// into the other layers of their stack.
+pub fn remove_constrained_tracks(scene: &ufbx::Scene, animation: &mut FbxAnimation) {
+    let constrained: Vec<usize> = evaluated_constraints(scene)
+        .iter()
+        .map(|(_, node, _)| node.element.typed_id as usize)
+        .collect();
+    animation
+        .tracks
+        .retain(|track| !constrained.contains(&track.node_index));
+}
@@ -20,6 +20,9 @@ pub enum FbxError {
     #[error("Failed to load texture: {0}")]
     TextureLoad(String),
 
+    #[error("Failed to load target skeleton: {0}")]
+    SkeletonLoad(String),
+
     #[error("Invalid FBX data: {0}")]
     InvalidData(String),
 
@@ -0,0 +1,120 @@
+//! Animation events from FBX timeline markers and event properties.
+
+use crate::types::FbxAnimationMarker;
+
+/// Collect the markers of an animation stack.
+///
+/// Markers come from two kinds of properties whose name starts with
+/// `prefix`, the rest of the name being the marker name:
+///
+/// - Animated properties of any element, with a marker at every key whose
+///   value is not zero. Keys of enum properties carry the name of the enum
+///   item as payload.
+/// - String properties of the stack, holding `;` separated `frame` or
+///   `frame:payload` entries on the timeline of the stack.
+///
+/// Times are in seconds from the start of the stack. Layers that key the
+/// same marker, such as an override layer copied from the base layer, add it
+/// only once.
+pub fn collect_markers(
+    stack: &ufbx::AnimStack,
+    frame_rate: f64,
+    prefix: &str,
+) -> Vec<FbxAnimationMarker> {
+    let mut markers = Vec::new();
+    if prefix.is_empty() {
+        return markers;
+    }
+
+    for layer in stack.layers.as_ref().iter() {
+        for anim_prop in layer.anim_props.as_ref().iter() {
+            let Some(name) = marker_name(anim_prop.prop_name.as_ref(), prefix) else {
+                continue;
+            };
+            let Some(curve) = anim_prop.anim_value.curves[0].as_ref() else {
+                continue;
+            };
+            let items = anim_prop
+                .element
+                .props
+                .find_prop(anim_prop.prop_name.as_ref())
+                .map(|prop| enum_items(prop.value_str.as_ref()))
+                .unwrap_or_default();
+
+            for keyframe in curve.keyframes.iter() {
+                if keyframe.value == 0.0 {
+                    continue;
+                }
+                markers.push(FbxAnimationMarker {
+                    time: (keyframe.time - stack.time_begin) as f32,
+                    name: name.to_string(),
+                    payload: items
+                        .get(keyframe.value.round() as usize)
+                        .map(|item| item.to_string()),
+                });
+            }
+        }
+    }
+
+    for prop in stack.element.props.props.as_ref().iter() {
+        if prop.type_ != ufbx::PropType::String {
+            continue;
+        }
+        let Some(name) = marker_name(prop.name.as_ref(), prefix) else {
+            continue;
+        };
+        for (frame, payload) in parse_marker_list(prop.value_str.as_ref()) {
+            markers.push(FbxAnimationMarker {
+                time: (frame / frame_rate - stack.time_begin) as f32,
+                name: name.to_string(),
+                payload,
+            });
+        }
+    }
+
+    markers.sort_by(|a, b| {
+        a.time
+            .total_cmp(&b.time)
+            .then_with(|| a.name.cmp(&b.name))
+            .then_with(|| a.payload.cmp(&b.payload))
+    });
+    markers.dedup();
+    markers
+}
+
+/// Marker name of a property, if its name starts with `prefix`.
+///
+/// Separators after the prefix are dropped, so `Event_Footstep` and
+/// `EventFootstep` both name `Footstep` markers.
+pub fn marker_name<'a>(prop_name: &'a str, prefix: &str) -> Option<&'a str> {
+    let name = prop_name
+        .strip_prefix(prefix)?
+        .trim_start_matches(['_', ':', ' ', '|']);
+    Some(if name.is_empty() { prop_name } else { name })
+}
+
+/// Parse `;` separated `frame` or `frame:payload` entries.
+///
+/// Entries that do not start with a frame number are skipped.
+pub fn parse_marker_list(value: &str) -> Vec<(f64, Option<String>)> {
+    value
+        .split(';')
+        .filter_map(|entry| {
+            let (frame, payload) = match entry.split_once(':') {
+                Some((frame, payload)) => (frame, Some(payload.trim().to_string())),
+                None => (entry, None),
+            };
+            let frame = frame.trim().parse().ok()?;
+            Some((frame, payload.filter(|payload| !payload.is_empty())))
+        })
+        .collect()
+}
+
+/// Items of an enum property, stored `~` separated in its string value.
+fn enum_items(value: &str) -> Vec<&str> {
+    if value.contains('~') {
+        value.split('~').collect()
+    } else {
+        Vec::new()
+    }
+}
@@ -0,0 +1,382 @@
+//! Vertex animation from geometry caches (`.mc`, `.pc2`) referenced by FBX
+//! cache deformers.
+
+use crate::error::FbxError;
+use crate::label::FbxAssetLabel;
+use crate::node::node_name;
+use crate::utils::convert_vec3;
+use bevy::asset::{Handle, LoadContext};
+use bevy::mesh::VertexAttributeValues;
+use bevy::prelude::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Per-frame vertex positions read from a geometry cache channel, such as a
+/// cloth or destruction simulation.
+///
+/// Frames hold one position per control point of the mesh, in the order of
+/// its [`Mesh::ATTRIBUTE_POSITION`].
+#[derive(Asset, Debug, Clone, TypePath)]
+pub struct FbxVertexAnimation {
+    /// Name of the cache channel
+    pub name: String,
+    /// Time of each frame in seconds from the first frame
+    pub times: Vec<f32>,
+    pub frames: Vec<Vec<Vec3>>,
+}
+
+impl FbxVertexAnimation {
+    /// Time of the last frame.
+    pub fn duration(&self) -> f32 {
+        self.times.last().copied().unwrap_or(0.0)
+    }
+
+    /// Positions at `time`, interpolated linearly between frames.
+    pub fn sample(&self, time: f32) -> Vec<Vec3> {
+        let next = self.times.partition_point(|&frame_time| frame_time <= time);
+        let (Some(previous), Some(frame)) = (next.checked_sub(1), self.frames.get(next)) else {
+            // Before the first or after the last frame
+            let index = next.min(self.frames.len().saturating_sub(1));
+            return self.frames.get(index).cloned().unwrap_or_default();
+        };
+
+        let span = self.times[next] - self.times[previous];
+        let t = if span > 0.0 {
+            (time - self.times[previous]) / span
+        } else {
+            0.0
+        };
+        self.frames[previous]
+            .iter()
+            .zip(frame)
+            .map(|(a, b)| a.lerp(*b, t))
+            .collect()
+    }
+}
+
+/// Plays an [`FbxVertexAnimation`] on the mesh of its entity, rewriting the
+/// vertex positions every frame.
+///
+/// Added to the mesh entities of nodes deformed by a geometry cache, playing
+/// on repeat.
+#[derive(Component, Reflect, Debug, Clone)]
+#[reflect(Component)]
+pub struct FbxVertexAnimationPlayer {
+    pub animation: Handle<FbxVertexAnimation>,
+    /// Playback time in seconds
+    pub time: f32,
+    pub speed: f32,
+    pub repeat: bool,
+    pub paused: bool,
+}
+
+impl FbxVertexAnimationPlayer {
+    pub fn new(animation: Handle<FbxVertexAnimation>) -> Self {
+        Self {
+            animation,
+            time: 0.0,
+            speed: 1.0,
+            repeat: true,
+            paused: false,
+        }
+    }
+}
+
+/// Asset path of a cache file, relative to the directory of the FBX file.
+///
+/// The relative file name is preferred, as absolute paths point into the
+/// machine the file was exported on.
+pub fn cache_file_path(file: &ufbx::CacheFile, directory: &Path) -> Option<String> {
+    let name = if !file.relative_filename.is_empty() {
+        file.relative_filename.as_ref()
+    } else {
+        file.absolute_filename
+            .as_ref()
+            .rsplit(['/', '\\'])
+            .next()
+            .unwrap_or_default()
+    };
+    if name.is_empty() {
+        return None;
+    }
+    Some(normalize_path(
+        &directory.join(normalize_path(name)).to_string_lossy(),
+    ))
+}
+
+/// Paths of the cache files referenced by the cache deformers of a scene.
+pub fn cache_file_paths(scene: &ufbx::Scene, directory: &Path) -> Vec<String> {
+    let mut paths = Vec::new();
+    for deformer in scene.cache_deformers.as_ref().iter() {
+        if let Some(path) = deformer
+            .file
+            .as_ref()
+            .and_then(|file| cache_file_path(file, directory))
+            && !paths.contains(&path)
+        {
+            paths.push(path);
+        }
+    }
+    paths
+}
+
+/// Read the geometry caches at `paths` through the asset reader.
+///
+/// Caches which cannot be read are skipped with a warning, so that a missing
+/// simulation does not fail the whole file. Returns the vertex animations of
+/// each cache by path.
+pub async fn load_geometry_caches(
+    paths: Vec<String>,
+    frames_per_second: f64,
+    load_context: &mut LoadContext<'_>,
+) -> HashMap<String, Vec<FbxVertexAnimation>> {
+    let mut files: HashMap<String, Arc<[u8]>> = HashMap::new();
+    let mut caches = HashMap::new();
+
+    for path in paths {
+        let mut result = Err(FbxError::InvalidData(path.clone()));
+        let mut missing = vec![path.clone()];
+
+        // Every attempt reads the files the previous one found missing, as
+        // caches storing a file per frame only name them in their header
+        while !missing.is_empty() {
+            for name in missing.drain(..) {
+                if files.contains_key(&name) {
+                    continue;
+                }
+                let bytes = match load_context.read_asset_bytes(name.clone()).await {
+                    Ok(bytes) => bytes,
+                    Err(error) => {
+                        warn!("Failed to read geometry cache file {}: {}", name, error);
+                        Vec::new()
+                    }
+                };
+                files.insert(name, bytes.into());
+            }
+            result = read_geometry_cache(&path, &files, frames_per_second, &mut missing);
+            missing.retain(|name| !files.contains_key(name));
+        }
+
+        match result {
+            Ok(animations) => {
+                caches.insert(path, animations);
+            }
+            Err(error) => warn!("Skipping geometry cache: {}", error),
+        }
+    }
+
+    caches
+}
+
+/// Read the vertex animations of every position channel of a geometry cache.
+///
+/// Files are served from `files` by path. Files the cache needs which are not
+/// in there are added to `missing`, so that they can be read and the cache
+/// read again.
+pub fn read_geometry_cache(
+    path: &str,
+    files: &HashMap<String, Arc<[u8]>>,
+    frames_per_second: f64,
+    missing: &mut Vec<String>,
+) -> Result<Vec<FbxVertexAnimation>, FbxError> {
+    let requested = RefCell::new(Vec::new());
+    let open_file = |name: &str, _: &ufbx::OpenFileInfo| {
+        let name = normalize_path(name);
+        match files.get(&name) {
+            Some(bytes) => Some(ufbx::Stream::Read(Box::new(Cursor::new(bytes.clone())))),
+            None => {
+                requested.borrow_mut().push(name);
+                None
+            }
+        }
+    };
+    let result = read_channels(path, &open_file, frames_per_second);
+    missing.extend(requested.into_inner());
+    result
+}
+
+fn read_channels(
+    path: &str,
+    open_file: &dyn Fn(&str, &ufbx::OpenFileInfo) -> Option<ufbx::Stream>,
+    frames_per_second: f64,
+) -> Result<Vec<FbxVertexAnimation>, FbxError> {
+    let cache = ufbx::load_geometry_cache(
+        path,
+        ufbx::GeometryCacheOpts {
+            open_file_cb: ufbx::OpenFileCb::Ref(open_file),
+            frames_per_second,
+            ..Default::default()
+        },
+    )
+    .map_err(|e| FbxError::UfbxError(format!("{}: {:?}", path, e)))?;
+
+    let mut animations = Vec::new();
+    for channel in cache.channels.as_ref().iter() {
+        if !matches!(
+            channel.interpretation,
+            ufbx::CacheInterpretation::Points | ufbx::CacheInterpretation::VertexPosition
+        ) {
+            continue;
+        }
+        let Some(first) = channel.frames.as_ref().first() else {
+            continue;
+        };
+
+        let mut times = Vec::new();
+        let mut frames = Vec::new();
+        for frame in channel.frames.as_ref().iter() {
+            let mut data = vec![ufbx::Vec3::default(); frame.data_count as usize];
+            let count = ufbx::read_geometry_cache_vec3(
+                frame,
+                &mut data,
+                ufbx::GeometryCacheDataOpts {
+                    open_file_cb: ufbx::OpenFileCb::Ref(open_file),
+                    ..Default::default()
+                },
+            );
+            if count != data.len() {
+                return Err(FbxError::InvalidData(format!(
+                    "{}: failed to read frame {} of channel {}",
+                    path,
+                    frame.time,
+                    channel.name.as_ref()
+                )));
+            }
+            times.push((frame.time - first.time) as f32);
+            frames.push(data.iter().map(convert_vec3).collect());
+        }
+
+        animations.push(FbxVertexAnimation {
+            name: channel.name.to_string(),
+            times,
+            frames,
+        });
+    }
+
+    Ok(animations)
+}
+
+/// Add the vertex animations of meshes deformed by geometry caches as labeled
+/// assets.
+///
+/// Returns the animation handles by node index.
+pub fn process_vertex_animations(
+    scene: &ufbx::Scene,
+    caches: &HashMap<String, Vec<FbxVertexAnimation>>,
+    directory: &Path,
+    load_context: &mut LoadContext,
+) -> HashMap<usize, Handle<FbxVertexAnimation>> {
+    let mut handles = HashMap::new();
+
+    for (index, node) in scene.nodes.as_ref().iter().enumerate() {
+        let Some(mesh) = node.mesh.as_ref() else {
+            continue;
+        };
+        let mut deformers = mesh.cache_deformers.as_ref().iter();
+        let Some(deformer) = deformers.next() else {
+            continue;
+        };
+        if deformers.next().is_some() {
+            warn!(
+                "Mesh {} has several geometry caches, only the first one is played",
+                node_name(node)
+            );
+        }
+
+        let Some(animations) = deformer
+            .file
+            .as_ref()
+            .and_then(|file| cache_file_path(file, directory))
+            .and_then(|path| caches.get(&path))
+        else {
+            continue;
+        };
+        // Single channel caches may leave the channel name out
+        let Some(animation) = animations
+            .iter()
+            .find(|animation| animation.name == deformer.channel.as_ref())
+            .or_else(|| animations.first().filter(|_| animations.len() == 1))
+        else {
+            warn!(
+                "Geometry cache of mesh {} has no channel {}",
+                node_name(node),
+                deformer.channel.as_ref()
+            );
+            continue;
+        };
+        if animation
+            .frames
+            .iter()
+            .any(|frame| frame.len() != mesh.num_vertices)
+        {
+            warn!(
+                "Geometry cache channel {} does not match the {} vertices of mesh {}",
+                animation.name,
+                mesh.num_vertices,
+                node_name(node)
+            );
+            continue;
+        }
+
+        let handle = load_context.add_labeled_asset(
+            FbxAssetLabel::VertexAnimation(index).to_string(),
+            animation.clone(),
+        );
+        handles.insert(index, handle);
+    }
+
+    handles
+}
+
+/// Advance vertex animation players and write the sampled positions into the
+/// meshes of their entities.
+pub fn play_fbx_vertex_animations(
+    time: Res<Time>,
+    animations: Res<Assets<FbxVertexAnimation>>,
+    meshes: Option<ResMut<Assets<Mesh>>>,
+    mut players: Query<(&mut FbxVertexAnimationPlayer, &Mesh3d)>,
+) {
+    // Meshes only exist with the mesh plugin
+    let Some(mut meshes) = meshes else {
+        return;
+    };
+
+    for (mut player, mesh) in &mut players {
+        if player.paused && !player.is_changed() {
+            continue;
+        }
+        let Some(animation) = animations.get(&player.animation) else {
+            continue;
+        };
+
+        if !player.paused {
+            let duration = animation.duration();
+            let time = player.time + time.delta_secs() * player.speed;
+            player.time = if player.repeat && duration > 0.0 {
+                time.rem_euclid(duration)
+            } else {
+                time.clamp(0.0, duration)
+            };
+        }
+
+        let Some(mut mesh) = meshes.get_mut(&mesh.0) else {
+            continue;
+        };
+        let Ok(VertexAttributeValues::Float32x3(positions)) =
+            mesh.try_attribute_mut(Mesh::ATTRIBUTE_POSITION)
+        else {
+            continue;
+        };
+        for (position, sampled) in positions.iter_mut().zip(animation.sample(player.time)) {
+            *position = sampled.to_array();
+        }
+    }
+}
+
+/// Use forward slashes, which both ufbx and asset paths understand.
+fn normalize_path(path: &str) -> String {
+    path.replace('\\', "/")
+}
@@ -0,0 +1,215 @@
+//! Humanoid bone classification for FBX skeletons.
+
+use crate::node::{animation_target_id, node_name};
+use crate::types::{
+    FbxHumanoid, FbxHumanoidJoint, FbxNode, HumanoidBone, HumanoidFinger, HumanoidNaming,
+    HumanoidSide,
+};
+use bevy::asset::Handle;
+use std::collections::HashMap;
+
+/// Bones every humanoid skeleton needs to drive a full body.
+pub const REQUIRED_HUMANOID_BONES: [HumanoidBone; 15] = [
+    HumanoidBone::Hips,
+    HumanoidBone::Spine,
+    HumanoidBone::Head,
+    HumanoidBone::UpperArm(HumanoidSide::Left),
+    HumanoidBone::LowerArm(HumanoidSide::Left),
+    HumanoidBone::Hand(HumanoidSide::Left),
+    HumanoidBone::UpperArm(HumanoidSide::Right),
+    HumanoidBone::LowerArm(HumanoidSide::Right),
+    HumanoidBone::Hand(HumanoidSide::Right),
+    HumanoidBone::UpperLeg(HumanoidSide::Left),
+    HumanoidBone::LowerLeg(HumanoidSide::Left),
+    HumanoidBone::Foot(HumanoidSide::Left),
+    HumanoidBone::UpperLeg(HumanoidSide::Right),
+    HumanoidBone::LowerLeg(HumanoidSide::Right),
+    HumanoidBone::Foot(HumanoidSide::Right),
+];
+
+/// Build the humanoid map of a skeleton from its joints.
+///
+/// Returns `None` if no hips are recognised. When joints follow several
+/// naming schemes, the scheme most joints follow wins, or on a tie the scheme
+/// of the first joint, and the first joint found for each bone is kept.
+pub fn create_humanoid(joints: &[(&ufbx::Node, Handle<FbxNode>)]) -> Option<FbxHumanoid> {
+    let classified: Vec<_> = joints
+        .iter()
+        .filter_map(|(node, handle)| {
+            let (naming, bone) = classify_bone(&node_name(node))?;
+            Some((naming, bone, *node, handle))
+        })
+        .collect();
+
+    // Counted in joint order so that ties go to the scheme found first
+    let mut counts: Vec<(HumanoidNaming, usize)> = Vec::new();
+    for (naming, ..) in &classified {
+        match counts.iter_mut().find(|(counted, _)| counted == naming) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((*naming, 1)),
+        }
+    }
+    let naming = counts
+        .into_iter()
+        .rev()
+        .max_by_key(|(_, count)| *count)
+        .map(|(naming, _)| naming)?;
+
+    let mut bones = HashMap::new();
+    for (bone_naming, bone, node, handle) in classified {
+        if bone_naming != naming {
+            continue;
+        }
+        bones.entry(bone).or_insert_with(|| FbxHumanoidJoint {
+            name: node_name(node),
+            node: handle.clone(),
+            target: animation_target_id(node),
+        });
+    }
+    if !bones.contains_key(&HumanoidBone::Hips) {
+        return None;
+    }
+
+    let complete = REQUIRED_HUMANOID_BONES
+        .iter()
+        .all(|bone| bones.contains_key(bone));
+    Some(FbxHumanoid {
+        naming,
+        bones,
+        complete,
+    })
+}
+
+/// Recognise the humanoid bone a joint name stands for.
+///
+/// Mixamo (`mixamorig:LeftForeArm`), HumanIK (`Character1_LeftForeArm`) and
+/// Biped (`Bip01 L Forearm`) names are supported. Returns the naming scheme
+/// along with the bone.
+pub fn classify_bone(name: &str) -> Option<(HumanoidNaming, HumanoidBone)> {
+    if let Some((namespace, local_name)) = name.rsplit_once(':') {
+        let naming = if namespace.starts_with("mixamorig") {
+            HumanoidNaming::Mixamo
+        } else {
+            HumanoidNaming::HumanIk
+        };
+        return classify_human_ik(local_name).map(|bone| (naming, bone));
+    }
+
+    if let Some((root, rest)) = name.split_once(' ')
+        && is_biped_root(root)
+    {
+        return classify_biped(rest).map(|bone| (HumanoidNaming::Biped, bone));
+    }
+
+    // HumanIK prefixes bones with the character name
+    let local_name = name
+        .split_once('_')
+        .filter(|(character, _)| character.starts_with("Character"))
+        .map_or(name, |(_, local_name)| local_name);
+    classify_human_ik(local_name).map(|bone| (HumanoidNaming::HumanIk, bone))
+}
+
+/// Whether a name is a Biped root such as `Bip01` or `Bip001`.
+fn is_biped_root(name: &str) -> bool {
+    name.strip_prefix("Bip")
+        .is_some_and(|number| !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Classify HumanIK names, which Mixamo uses as well.
+fn classify_human_ik(name: &str) -> Option<HumanoidBone> {
+    let bone = match name {
+        "Hips" => HumanoidBone::Hips,
+        "Spine" => HumanoidBone::Spine,
+        "Spine1" => HumanoidBone::Chest,
+        "Spine2" => HumanoidBone::UpperChest,
+        "Neck" => HumanoidBone::Neck,
+        "Head" => HumanoidBone::Head,
+        _ => {
+            let (side, part) = if let Some(part) = name.strip_prefix("Left") {
+                (HumanoidSide::Left, part)
+            } else {
+                (HumanoidSide::Right, name.strip_prefix("Right")?)
+            };
+            match part {
+                "Shoulder" => HumanoidBone::Shoulder(side),
+                "Arm" => HumanoidBone::UpperArm(side),
+                "ForeArm" => HumanoidBone::LowerArm(side),
+                "Hand" => HumanoidBone::Hand(side),
+                "UpLeg" => HumanoidBone::UpperLeg(side),
+                "Leg" => HumanoidBone::LowerLeg(side),
+                "Foot" => HumanoidBone::Foot(side),
+                "ToeBase" => HumanoidBone::Toes(side),
+                _ => {
+                    let finger = part.strip_prefix("Hand")?;
+                    let split = finger
+                        .len()
+                        .checked_sub(1)
+                        .filter(|&split| finger.is_char_boundary(split))?;
+                    let (finger, segment) = finger.split_at(split);
+                    let finger = match finger {
+                        "Thumb" => HumanoidFinger::Thumb,
+                        "Index" => HumanoidFinger::Index,
+                        "Middle" => HumanoidFinger::Middle,
+                        "Ring" => HumanoidFinger::Ring,
+                        "Pinky" => HumanoidFinger::Little,
+                        _ => return None,
+                    };
+                    // The fourth segment is the finger tip end bone
+                    let segment: u8 = segment.parse().ok().filter(|s| (1..=3).contains(s))?;
+                    HumanoidBone::Finger(side, finger, segment)
+                }
+            }
+        }
+    };
+    Some(bone)
+}
+
+/// Classify 3ds Max Biped names, without the `Bip01` root.
+fn classify_biped(name: &str) -> Option<HumanoidBone> {
+    let bone = match name {
+        "Pelvis" => HumanoidBone::Hips,
+        "Spine" => HumanoidBone::Spine,
+        "Spine1" => HumanoidBone::Chest,
+        "Spine2" => HumanoidBone::UpperChest,
+        "Neck" => HumanoidBone::Neck,
+        "Head" => HumanoidBone::Head,
+        _ => {
+            let (side, part) = name.split_once(' ')?;
+            let side = match side {
+                "L" => HumanoidSide::Left,
+                "R" => HumanoidSide::Right,
+                _ => return None,
+            };
+            match part {
+                "Clavicle" => HumanoidBone::Shoulder(side),
+                "UpperArm" => HumanoidBone::UpperArm(side),
+                "Forearm" => HumanoidBone::LowerArm(side),
+                "Hand" => HumanoidBone::Hand(side),
+                "Thigh" => HumanoidBone::UpperLeg(side),
+                "Calf" => HumanoidBone::LowerLeg(side),
+                "Foot" => HumanoidBone::Foot(side),
+                "Toe0" => HumanoidBone::Toes(side),
+                _ => {
+                    // `Finger0` is the thumb root, `Finger02` its third segment
+                    let digits = part.strip_prefix("Finger")?.as_bytes();
+                    let finger = match digits.first()? {
+                        b'0' => HumanoidFinger::Thumb,
+                        b'1' => HumanoidFinger::Index,
+                        b'2' => HumanoidFinger::Middle,
+                        b'3' => HumanoidFinger::Ring,
+                        b'4' => HumanoidFinger::Little,
+                        _ => return None,
+                    };
+                    let segment = match digits.get(1..)? {
+                        [] => 1,
+                        [b'1'] => 2,
+                        [b'2'] => 3,
+                        _ => return None,
+                    };
+                    HumanoidBone::Finger(side, finger, segment)
+                }
+            }
+        }
+    };
+    Some(bone)
+}
@@ -12,14 +12,32 @@ pub enum FbxAssetLabel {
     Material(usize),
     /// `Animation{}`: FBX animation as a Bevy [`AnimationClip`](bevy::animation::AnimationClip)
     Animation(usize),
-    /// `AnimationStack{}`: FBX animation stack with multiple layers
+    /// `AnimationGraph{}`: Animation graph of a scene with every animation clip
+    AnimationGraph(usize),
+    /// `AnimationStack{}`: FBX animation stack with multiple layers, as a Bevy
+    /// [`AnimationGraph`](bevy::animation::graph::AnimationGraph) blending its layers
     AnimationStack(usize),
+    /// `Animation{}Layer{}`: Single layer of an FBX animation stack as a Bevy
+    /// [`AnimationClip`](bevy::animation::AnimationClip)
+    AnimationLayer(usize, usize),
+    /// `AnimationClip{}`: Clip cut out of an FBX animation stack by frame range, as a
+    /// Bevy [`AnimationClip`](bevy::animation::AnimationClip)
+    AnimationClip(usize),
+    /// `RootMotion{}`: Root motion extracted from an FBX animation stack
+    RootMotion(usize),
+    /// `AnimationClip{}RootMotion`: Root motion extracted from a clip cut out of an
+    /// FBX animation stack
+    AnimationClipRootMotion(usize),
+    /// `VertexAnimation{}`: Vertex animation read from the geometry cache of node `{}`
+    VertexAnimation(usize),
     /// `Skeleton{}`: FBX skeleton for skeletal animation
     Skeleton(usize),
     /// `Node{}`: Individual FBX node in the scene hierarchy
     Node(usize),
     /// `Skin{}`: FBX skin for skeletal animation
     Skin(usize),
+    /// `Humanoid{}`: Humanoid bones recognised among the joints of skeleton `{}`
+    Humanoid(usize),
     /// `Light{}`: FBX light definition
     Light(usize),
     /// `Camera{}`: FBX camera definition
@@ -41,10 +59,23 @@ impl core::fmt::Display for FbxAssetLabel {
             FbxAssetLabel::Mesh(index) => f.write_str(&format!("Mesh{index}")),
             FbxAssetLabel::Material(index) => f.write_str(&format!("Material{index}")),
             FbxAssetLabel::Animation(index) => f.write_str(&format!("Animation{index}")),
+            FbxAssetLabel::AnimationGraph(index) => f.write_str(&format!("AnimationGraph{index}")),
             FbxAssetLabel::AnimationStack(index) => f.write_str(&format!("AnimationStack{index}")),
+            FbxAssetLabel::AnimationLayer(stack, layer) => {
+                f.write_str(&format!("Animation{stack}Layer{layer}"))
+            }
+            FbxAssetLabel::AnimationClip(index) => f.write_str(&format!("AnimationClip{index}")),
+            FbxAssetLabel::RootMotion(index) => f.write_str(&format!("RootMotion{index}")),
+            FbxAssetLabel::AnimationClipRootMotion(index) => {
+                f.write_str(&format!("AnimationClip{index}RootMotion"))
+            }
+            FbxAssetLabel::VertexAnimation(index) => {
+                f.write_str(&format!("VertexAnimation{index}"))
+            }
             FbxAssetLabel::Skeleton(index) => f.write_str(&format!("Skeleton{index}")),
             FbxAssetLabel::Node(index) => f.write_str(&format!("Node{index}")),
             FbxAssetLabel::Skin(index) => f.write_str(&format!("Skin{index}")),
+            FbxAssetLabel::Humanoid(index) => f.write_str(&format!("Humanoid{index}")),
             FbxAssetLabel::Light(index) => f.write_str(&format!("Light{index}")),
             FbxAssetLabel::Camera(index) => f.write_str(&format!("Camera{index}")),
             FbxAssetLabel::Texture(index) => f.write_str(&format!("Texture{index}")),
@@ -14,28 +14,56 @@
 //! - Scene hierarchy with nodes
 //! - Lights and cameras
 //! - Animation clips with curves
+//! - Vertex animation from geometry caches
 
+use bevy::app::AnimationSystems;
 use bevy::asset::AssetApp;
+use bevy::camera::visibility::VisibilitySystems;
 use bevy::prelude::*;
+use constraint::evaluate_fbx_constraints;
+use geometry_cache::play_fbx_vertex_animations;
+use material::apply_fbx_material_animation;
+use property::apply_fbx_visibility;
 
+pub mod animation;
+pub mod constraint;
 pub mod error;
+pub mod events;
+pub mod geometry_cache;
+pub mod humanoid;
 pub mod label;
 pub mod loader;
 pub mod material;
 pub mod mesh;
 pub mod node;
+pub mod pose;
+pub mod property;
+pub mod reduction;
+pub mod retarget;
+pub mod root_motion;
 pub mod scene;
 pub mod types;
 pub mod utils;
 
+pub use constraint::FbxConstraint;
 pub use error::FbxError;
+pub use geometry_cache::{FbxVertexAnimation, FbxVertexAnimationPlayer};
 pub use label::FbxAssetLabel;
-pub use loader::{FbxLoader, FbxLoaderSettings};
+pub use loader::{
+    FbxAnimationSettings, FbxClipSettings, FbxKeyReductionSettings, FbxLoader, FbxLoaderSettings,
+    FbxRetargetSettings, FbxSkinningSettings,
+};
+pub use material::FbxMaterialAnimation;
+pub use pose::FbxPose;
+pub use property::{FbxUserProperties, FbxVisibility};
 pub use types::*;
 
 pub mod prelude {
     //! Commonly used items.
-    pub use crate::{Fbx, FbxAssetLabel, FbxLoaderSettings, FbxNode, FbxPlugin, FbxSkin, Skeleton};
+    pub use crate::{
+        Fbx, FbxAnimationEvent, FbxAssetLabel, FbxLoaderSettings, FbxNode, FbxPlugin, FbxSkin,
+        FbxUserProperties, Skeleton,
+    };
 }
 
 /// Plugin adding the FBX loader to an [`App`].
@@ -48,6 +76,27 @@ impl Plugin for FbxPlugin {
             .init_asset::<FbxNode>()
             .init_asset::<FbxSkin>()
             .init_asset::<Skeleton>()
-            .register_asset_loader(FbxLoader::default());
+            .init_asset::<FbxRootMotion>()
+            .init_asset::<FbxHumanoid>()
+            .init_asset::<FbxVertexAnimation>()
+            .register_type::<FbxVisibility>()
+            .register_type::<FbxUserProperties>()
+            .register_type::<FbxMaterialAnimation>()
+            .register_type::<FbxConstraint>()
+            .register_type::<FbxVertexAnimationPlayer>()
+            .register_asset_loader(FbxLoader::default())
+            .add_systems(
+                PostUpdate,
+                (
+                    apply_fbx_visibility
+                        .after(AnimationSystems)
+                        .before(VisibilitySystems::VisibilityPropagate),
+                    apply_fbx_material_animation.after(AnimationSystems),
+                    evaluate_fbx_constraints
+                        .after(AnimationSystems)
+                        .before(TransformSystems::Propagate),
+                    play_fbx_vertex_animations,
+                ),
+            );
     }
 }
@@ -1,21 +1,27 @@
 //! FBX loader implementation for Bevy.
 
+use crate::animation::{create_animation_graph, process_animations, FbxAnimationAssets};
 use crate::error::FbxError;
+use crate::geometry_cache::{cache_file_paths, load_geometry_caches, process_vertex_animations};
 use crate::material::process_materials;
 use crate::mesh::process_meshes;
-use crate::node::{process_nodes, process_skins};
-use crate::scene::build_scene;
+use crate::node::{process_nodes, process_skeletons, process_skins};
+use crate::pose::FbxPoseData;
+use crate::scene::{build_scene, SceneContext};
 use crate::types::{Fbx, FbxAxisSystem, FbxMeta, Handedness};
+use crate::utils::convert_scene_settings;
 use bevy::asset::{io::Reader, AssetLoader, LoadContext, RenderAssetUsages};
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
 
 /// Settings for FBX file loading.
 ///
 /// These settings allow customizing which parts of the FBX file are loaded
 /// and how they are processed.
 #[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct FbxLoaderSettings {
     /// How meshes should be loaded and used
     pub load_meshes: RenderAssetUsages,
@@ -25,10 +31,19 @@ pub struct FbxLoaderSettings {
     pub load_cameras: bool,
     /// Whether to load lights from the FBX file
     pub load_lights: bool,
-    /// Whether to include raw source data in the loaded asset
+    /// Whether to keep the parsed scene in the loaded asset, so that poses
+    /// are sampled from the FBX curves rather than the converted ones
     pub include_source: bool,
     /// Whether to convert coordinate systems (e.g., Y-up to Z-up)
     pub convert_coordinates: bool,
+    /// Whether to only import animations, skipping geometry, materials and the scene
+    pub animation_only: bool,
+    /// Index of the animation stack to play on repeat when the scene is spawned
+    pub autoplay_animation: Option<usize>,
+    /// How animation curves are baked into keyframes
+    pub animation: FbxAnimationSettings,
+    /// How skin weights are limited to the influences Bevy supports
+    pub skinning: FbxSkinningSettings,
 }
 
 impl Default for FbxLoaderSettings {
@@ -40,10 +55,151 @@ impl Default for FbxLoaderSettings {
             load_lights: true,
             include_source: false,
             convert_coordinates: false,
+            animation_only: false,
+            autoplay_animation: None,
+            animation: FbxAnimationSettings::default(),
+            skinning: FbxSkinningSettings::default(),
         }
     }
 }
 
+/// Settings for baking FBX animation curves into keyframes.
+///
+/// FBX curves may use any Euler rotation order, pre/post extrapolation and
+/// layer blending, so they are evaluated by ufbx and resampled into keys that
+/// map directly onto Bevy curves.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FbxAnimationSettings {
+    /// Frame rate used to resample non-linear curves, in frames per second
+    pub sample_rate: f64,
+    /// Whether to blend all layers of a stack together, or only bake the base layer
+    pub bake_layers: bool,
+    /// Maximum error for dropping resampled keys that can be interpolated, `0.0` keeps every key
+    pub resample_tolerance: f64,
+    /// Whether to keep stepped and cubic keys as authored where Bevy curves can represent them
+    pub preserve_interpolation: bool,
+    /// Named clips to cut out of the animation stacks by frame range
+    pub clips: Vec<FbxClipSettings>,
+    /// Name of the joint whose horizontal translation and yaw are moved into a
+    /// separate root motion asset
+    pub root_motion_joint: Option<String>,
+    /// FBX file, relative to the loaded file, whose node hierarchy animation
+    /// targets are matched against by node name
+    pub target_skeleton: Option<String>,
+    /// Retarget animations onto `target_skeleton` instead of binding them by name
+    pub retarget: Option<FbxRetargetSettings>,
+    /// Name prefix of the properties turned into animation events, empty to skip events
+    pub event_prefix: String,
+    /// Bake constraints into the node keyframes instead of spawning
+    /// [`FbxConstraint`](crate::constraint::FbxConstraint) components
+    pub bake_constraints: bool,
+    /// Remove node keys of the generated clips which the remaining keys
+    /// reproduce within these tolerances
+    pub key_reduction: Option<FbxKeyReductionSettings>,
+}
+
+impl Default for FbxAnimationSettings {
+    fn default() -> Self {
+        Self {
+            sample_rate: 30.0,
+            bake_layers: true,
+            resample_tolerance: 0.0,
+            preserve_interpolation: true,
+            clips: Vec::new(),
+            root_motion_joint: None,
+            target_skeleton: None,
+            retarget: None,
+            event_prefix: "Event".to_string(),
+            bake_constraints: false,
+            key_reduction: None,
+        }
+    }
+}
+
+/// A named clip cut out of an animation stack.
+///
+/// Frames are counted in the frame rate of the FBX file on the timeline of the
+/// stack, so they match the frame numbers shown in the authoring tool.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FbxClipSettings {
+    /// Name of the clip in `Fbx::named_animations`
+    pub name: String,
+    /// Index of the animation stack the clip is cut from
+    #[serde(default)]
+    pub stack: usize,
+    /// First frame of the clip
+    pub start_frame: i64,
+    /// Last frame of the clip, inclusive
+    pub end_frame: i64,
+    /// Whether the clip is meant to play on repeat
+    #[serde(default, rename = "loop")]
+    pub looping: bool,
+}
+
+/// Influence limits of skinned vertices.
+///
+/// Each vertex keeps its largest joint weights, renormalized to sum to one.
+/// A warning reports the meshes whose vertices lost weights to the limit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FbxSkinningSettings {
+    /// Maximum number of joints influencing a vertex, at most four
+    pub max_influences: usize,
+    /// Weights at or below this value are pruned before the limit is applied
+    pub min_weight: f32,
+}
+
+impl Default for FbxSkinningSettings {
+    fn default() -> Self {
+        Self {
+            max_influences: 4,
+            min_weight: 0.0,
+        }
+    }
+}
+
+/// Error tolerances of keyframe reduction.
+///
+/// Keys are only removed when the curve through the remaining keys stays
+/// within the tolerance of their value. A tolerance of `0.0` keeps every key
+/// of that channel.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FbxKeyReductionSettings {
+    /// Maximum translation error, in meters
+    pub translation: f32,
+    /// Maximum rotation error, in degrees
+    pub rotation: f32,
+    /// Maximum scale error
+    pub scale: f32,
+}
+
+impl Default for FbxKeyReductionSettings {
+    fn default() -> Self {
+        Self {
+            translation: 0.001,
+            rotation: 0.1,
+            scale: 0.001,
+        }
+    }
+}
+
+/// Settings for retargeting animations onto a rig with other bone names and
+/// another rest pose.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FbxRetargetSettings {
+    /// Target bone name for each source bone name, other bones are paired by name
+    pub bone_map: HashMap<String, String>,
+    /// Extra rotation of target bones whose rest pose differs from the source,
+    /// as XYZ Euler angles in degrees by target bone name
+    pub rest_corrections: HashMap<String, [f32; 3]>,
+    /// Source bone whose translation is transferred, by default the topmost
+    /// bone with animated translation
+    pub root_bone: Option<String>,
+}
+
 /// Loader implementation for FBX files.
 ///
 /// This loader handles reading FBX files and converting them into Bevy assets,
@@ -74,53 +230,149 @@ impl AssetLoader for FbxLoader {
             return Err(FbxError::InvalidData("FBX file too small".to_string()));
         }
 
+        // Load the skeleton animations are bound to. Borrows of ufbx scenes
+        // are not `Send`, so only owned scene roots are held across awaits and
+        // the scenes are borrowed once every file is read
+        let skeleton_root = match &settings.animation.target_skeleton {
+            Some(path) => Some(load_target_skeleton(path, load_context).await?),
+            None => None,
+        };
+
         // Parse with ufbx
         let root = ufbx::load_memory(
             &bytes,
             ufbx::LoadOpts {
                 target_unit_meters: 1.0,
                 target_axes: ufbx::CoordinateAxes::right_handed_y_up(),
+                // FBX cameras look down +X and lights point down -Y, Bevy
+                // uses -Z for both
+                target_camera_axes: ufbx::CoordinateAxes::right_handed_y_up(),
+                target_light_axes: ufbx::CoordinateAxes::right_handed_y_up(),
+                ignore_geometry: settings.animation_only,
                 ..Default::default()
             },
         )
         .map_err(|e| FbxError::UfbxError(format!("{:?}", e)))?;
+
+        // Read the geometry caches of cache deformers, next to the FBX file.
+        // The scene is only borrowed to find their paths, before the await
+        let directory = load_context
+            .path()
+            .path()
+            .parent()
+            .map(std::path::Path::to_path_buf)
+            .unwrap_or_default();
+        let (cache_paths, frames_per_second) = if !settings.animation_only {
+            let scene: &ufbx::Scene = &root;
+            (
+                cache_file_paths(scene, &directory),
+                scene.settings.frames_per_second,
+            )
+        } else {
+            (Vec::new(), 0.0)
+        };
+        let geometry_caches =
+            load_geometry_caches(cache_paths, frames_per_second, load_context).await;
+
+        // Every file is read, so the scene can be borrowed from here on
         let scene: &ufbx::Scene = &*root;
 
         // Process meshes
-        let (meshes, named_meshes, mesh_transforms, mesh_material_info) =
-            process_meshes(scene, settings, load_context)?;
-
-        // Process materials and textures
-        let (materials, named_materials) = if !settings.load_materials.is_empty() {
-            process_materials(scene, settings, load_context)?
+        let (
+            meshes,
+            named_meshes,
+            mesh_nodes,
+            mesh_material_info,
+            joint_palettes,
+            skinning_warnings,
+        ) = if !settings.animation_only {
+            process_meshes(scene, settings, load_context)?
         } else {
-            (Vec::new(), HashMap::new())
+            (
+                Vec::new(),
+                HashMap::new(),
+                Vec::new(),
+                Vec::new(),
+                Vec::new(),
+                Vec::new(),
+            )
         };
 
+        let vertex_animations =
+            process_vertex_animations(scene, &geometry_caches, &directory, load_context);
+
+        // Process materials and textures
+        let (materials, named_materials) =
+            if !settings.load_materials.is_empty() && !settings.animation_only {
+                process_materials(scene, settings, load_context)?
+            } else {
+                (Vec::new(), HashMap::new())
+            };
+
         // Process nodes and hierarchy
         let (nodes, named_nodes, node_map) = process_nodes(scene, &meshes, load_context)?;
 
+        // Skeletons are kept for animation-only files too, as they describe
+        // the joints the animations target
+        let (skeletons, humanoids, joint_skeletons) =
+            process_skeletons(scene, &nodes, load_context);
+
         // Process skins
-        let (skins, named_skins) = process_skins(scene, &node_map, load_context)?;
+        let (skins, named_skins) = if !settings.animation_only {
+            process_skins(scene, &node_map, &joint_skeletons, load_context)?
+        } else {
+            (Vec::new(), HashMap::new())
+        };
+
+        // Process animations
+        let FbxAnimationAssets {
+            animations,
+            named_animations,
+            animation_stacks,
+            named_animation_stacks,
+            animation_clips,
+            root_motions,
+            key_reduction,
+            stack_animations,
+            unmatched_bones,
+        } = process_animations(scene, skeleton_root.as_deref(), settings, load_context)?;
+        let (animation_graph, animation_graph_nodes) = if !animations.is_empty() {
+            let (graph, graph_nodes) = create_animation_graph(&animations, load_context);
+            (Some(graph), graph_nodes)
+        } else {
+            (None, Vec::new())
+        };
 
         // Build scene
-        let scene_handle = build_scene(
-            scene,
-            &meshes,
-            &materials,
-            &named_materials,
-            &mesh_transforms,
-            &mesh_material_info,
-            settings,
-            load_context,
-        )?;
+        let scene_handle = if !settings.animation_only {
+            let context = SceneContext {
+                meshes: &meshes,
+                materials: &materials,
+                named_materials: &named_materials,
+                mesh_nodes: &mesh_nodes,
+                mesh_material_info: &mesh_material_info,
+                vertex_animations: &vertex_animations,
+                joint_palettes: &joint_palettes,
+                animation_graph: animation_graph.as_ref(),
+                animation_graph_nodes: &animation_graph_nodes,
+            };
+            Some(build_scene(scene, &context, settings, load_context)?)
+        } else {
+            None
+        };
 
         // Extract metadata
         let metadata = FbxMeta::default();
 
+        let scene_settings = convert_scene_settings(scene);
+        let mut poses = FbxPoseData::new(scene, stack_animations);
+        if settings.include_source {
+            poses.source = Some(Arc::new(root));
+        }
+
         // Build final FBX asset
         Ok(Fbx {
-            scenes: vec![scene_handle.clone()],
+            scenes: scene_handle.iter().cloned().collect(),
             named_scenes: HashMap::new(),
             meshes,
             named_meshes,
@@ -128,15 +380,31 @@ impl AssetLoader for FbxLoader {
             named_materials,
             nodes,
             named_nodes,
+            skeletons,
             skins,
             named_skins,
-            default_scene: Some(scene_handle),
+            humanoids,
+            skinning_warnings,
+            animations,
+            named_animations,
+            animation_graph,
+            animation_graph_nodes,
+            animation_stacks,
+            named_animation_stacks,
+            animation_clips,
+            root_motions,
+            vertex_animations: vertex_animations.into_values().collect(),
+            unmatched_bones,
+            key_reduction,
+            poses,
+            default_scene: scene_handle,
             axis_system: FbxAxisSystem {
                 up: Vec3::Y,
                 front: Vec3::Z,
                 handedness: Handedness::Right,
             },
             unit_scale: 1.0,
+            scene_settings,
             metadata,
         })
     }
@@ -145,3 +413,36 @@ impl AssetLoader for FbxLoader {
         &["fbx"]
     }
 }
+
+/// Read the FBX file whose node hierarchy animations are bound to.
+///
+/// Only the node hierarchy is needed, so geometry and animation are skipped.
+async fn load_target_skeleton(
+    path: &str,
+    load_context: &mut LoadContext<'_>,
+) -> Result<ufbx::SceneRoot, FbxError> {
+    let skeleton_path = load_context
+        .path()
+        .path()
+        .parent()
+        .unwrap_or_else(|| std::path::Path::new(""))
+        .join(path);
+    let bytes = load_context
+        .read_asset_bytes(skeleton_path)
+        .await
+        .map_err(|e| FbxError::SkeletonLoad(format!("{}: {}", path, e)))?;
+
+    ufbx::load_memory(
+        &bytes,
+        ufbx::LoadOpts {
+            target_unit_meters: 1.0,
+            target_axes: ufbx::CoordinateAxes::right_handed_y_up(),
+            target_camera_axes: ufbx::CoordinateAxes::right_handed_y_up(),
+            target_light_axes: ufbx::CoordinateAxes::right_handed_y_up(),
+            ignore_geometry: true,
+            ignore_animation: true,
+            ..Default::default()
+        },
+    )
+    .map_err(|e| FbxError::SkeletonLoad(format!("{}: {:?}", path, e)))
+}
@@ -3,8 +3,13 @@
 use crate::error::FbxError;
 use crate::label::FbxAssetLabel;
 use crate::loader::FbxLoaderSettings;
-use crate::utils::convert_texture_uv_transform;
+use crate::property::{color_curve, float_curve, vec2_curve};
+use crate::types::{FbxKeyframes, FbxMaterialProperty, FbxMaterialTrack};
+use crate::utils::{convert_texture_uv_parts, convert_texture_uv_transform, convert_vec3};
+use bevy::animation::{animated_field, AnimationTargetId, VariableCurve};
 use bevy::asset::{Handle, LoadContext};
+use bevy::color::ColorToComponents;
+use bevy::math::Affine2;
 use bevy::pbr::StandardMaterial;
 use bevy::prelude::*;
 use bevy::material::AlphaMode;
@@ -197,3 +202,278 @@ pub fn create_standard_material(
 
     Ok(material)
 }
+
+/// Texture whose UV transform is used by the material, bound to its base color.
+fn base_color_texture(material: &ufbx::Material) -> Option<&ufbx::Texture> {
+    material
+        .textures
+        .iter()
+        .find(|texture_ref| {
+            matches!(
+                texture_ref.material_prop.as_ref(),
+                "DiffuseColor" | "BaseColor"
+            )
+        })
+        .map(|texture_ref| &*texture_ref.texture)
+}
+
+/// Compute the animation target id of a material from its name.
+pub fn material_target_id(material: &ufbx::Material) -> AnimationTargetId {
+    let name = if material.element.name.is_empty() {
+        format!("Material_{}", material.element.typed_id)
+    } else {
+        material.element.name.to_string()
+    };
+    AnimationTargetId::from_names([Name::new("Material"), Name::new(name)].iter())
+}
+
+/// Whether any animation stack animates a material or its base color texture.
+pub fn is_material_animated(scene: &ufbx::Scene, material: &ufbx::Material) -> bool {
+    !animated_material_properties(scene, material).is_empty()
+}
+
+/// Material properties animated by any animation stack, in the order of
+/// [`FbxMaterialProperty`].
+pub fn animated_material_properties(
+    scene: &ufbx::Scene,
+    material: &ufbx::Material,
+) -> Vec<FbxMaterialProperty> {
+    let texture_id = base_color_texture(material).map(|texture| texture.element.element_id);
+    let mut properties: Vec<_> = scene
+        .anim_stacks
+        .as_ref()
+        .iter()
+        .flat_map(|stack| stack.layers.as_ref().iter())
+        .flat_map(|layer| layer.anim_props.as_ref().iter())
+        .filter_map(|anim_prop| {
+            let element_id = anim_prop.element.element_id;
+            if element_id == material.element.element_id {
+                material_property(anim_prop.prop_name.as_ref())
+            } else if Some(element_id) == texture_id {
+                texture_property(anim_prop.prop_name.as_ref())
+            } else {
+                None
+            }
+        })
+        .collect();
+    properties.sort_by_key(|property| *property as u8);
+    properties.dedup();
+    properties
+}
+
+/// Material property animated by an FBX material property.
+fn material_property(name: &str) -> Option<FbxMaterialProperty> {
+    match name {
+        "DiffuseColor" => Some(FbxMaterialProperty::BaseColor),
+        "EmissiveColor" => Some(FbxMaterialProperty::EmissiveColor),
+        _ => None,
+    }
+}
+
+/// Material property animated by an FBX base color texture property.
+fn texture_property(name: &str) -> Option<FbxMaterialProperty> {
+    match name {
+        "Translation" => Some(FbxMaterialProperty::UvOffset),
+        "Scaling" => Some(FbxMaterialProperty::UvScale),
+        "Rotation" => Some(FbxMaterialProperty::UvRotation),
+        _ => None,
+    }
+}
+
+/// Bake the animated colors and base color UV transforms of every material
+/// into tracks.
+pub fn bake_material_tracks(
+    scene: &ufbx::Scene,
+    baked_elements: &HashMap<u32, &ufbx::BakedElement>,
+) -> Vec<FbxMaterialTrack> {
+    let mut tracks = Vec::new();
+
+    for material in scene.materials.as_ref().iter() {
+        let mut push = |property: FbxMaterialProperty, keys: &[ufbx::BakedVec3]| {
+            tracks.push(FbxMaterialTrack {
+                material_index: material.element.typed_id as usize,
+                target: material_target_id(material),
+                property,
+                keyframes: FbxKeyframes {
+                    times: keys.iter().map(|key| key.time as f32).collect(),
+                    values: keys
+                        .iter()
+                        .map(|key| convert_material_value(property, convert_vec3(&key.value)))
+                        .collect(),
+                    ..Default::default()
+                },
+            });
+        };
+
+        if let Some(baked) = baked_elements.get(&material.element.element_id) {
+            for prop in baked.props.iter() {
+                if let Some(property) = material_property(prop.name.as_ref()) {
+                    push(property, &prop.keys);
+                }
+            }
+        }
+
+        let Some(baked) = base_color_texture(material)
+            .and_then(|texture| baked_elements.get(&texture.element.element_id))
+        else {
+            continue;
+        };
+        for prop in baked.props.iter() {
+            if let Some(property) = texture_property(prop.name.as_ref()) {
+                push(property, &prop.keys);
+            }
+        }
+    }
+
+    tracks
+}
+
+/// Convert an FBX material or texture property value into the units of its
+/// track.
+fn convert_material_value(property: FbxMaterialProperty, value: Vec3) -> Vec3 {
+    match property {
+        FbxMaterialProperty::BaseColor => {
+            Color::srgb(value.x, value.y, value.z).to_linear().to_vec3()
+        }
+        FbxMaterialProperty::EmissiveColor
+        | FbxMaterialProperty::UvOffset
+        | FbxMaterialProperty::UvScale => value,
+        // UV rotation is around the Z axis, in degrees
+        FbxMaterialProperty::UvRotation => Vec3::new(value.z.to_radians(), 0.0, 0.0),
+    }
+}
+
+/// Value of a material property stored in the file, in the units of its
+/// track.
+pub fn rest_material_value(material: &ufbx::Material, property: FbxMaterialProperty) -> Vec3 {
+    let xyz = |v: ufbx::Vec4| Vec3::new(v.x as f32, v.y as f32, v.z as f32);
+    let texture_prop = |name: &str, default: Vec3| {
+        base_color_texture(material)
+            .and_then(|texture| texture.element.props.find_prop(name))
+            .map_or(default, |prop| xyz(prop.value_vec4))
+    };
+    let value = match property {
+        FbxMaterialProperty::BaseColor => xyz(material.fbx.diffuse_color.value_vec4),
+        FbxMaterialProperty::EmissiveColor => xyz(material.fbx.emission_color.value_vec4),
+        FbxMaterialProperty::UvOffset => texture_prop("Translation", Vec3::ZERO),
+        FbxMaterialProperty::UvScale => texture_prop("Scaling", Vec3::ONE),
+        FbxMaterialProperty::UvRotation => texture_prop("Rotation", Vec3::ZERO),
+    };
+    convert_material_value(property, value)
+}
+
+/// Create a curve animating the material property of a track.
+pub fn material_curve(track: &FbxMaterialTrack) -> Option<VariableCurve> {
+    let keyframes = &track.keyframes;
+    match track.property {
+        FbxMaterialProperty::BaseColor => {
+            color_curve(animated_field!(FbxMaterialAnimation::base_color), keyframes)
+        }
+        FbxMaterialProperty::EmissiveColor => {
+            color_curve(animated_field!(FbxMaterialAnimation::emissive), keyframes)
+        }
+        FbxMaterialProperty::UvOffset => {
+            vec2_curve(animated_field!(FbxMaterialAnimation::uv_offset), keyframes)
+        }
+        FbxMaterialProperty::UvScale => {
+            vec2_curve(animated_field!(FbxMaterialAnimation::uv_scale), keyframes)
+        }
+        FbxMaterialProperty::UvRotation => float_curve(
+            animated_field!(FbxMaterialAnimation::uv_rotation),
+            keyframes,
+        ),
+    }
+}
+
+/// Animated values of the [`StandardMaterial`] of a mesh entity, copied into
+/// the material by [`apply_fbx_material_animation`].
+///
+/// Materials are shared assets, so every entity using an animated material
+/// shows the animation.
+#[derive(Component, Reflect, Debug, Clone, PartialEq)]
+#[reflect(Component)]
+pub struct FbxMaterialAnimation {
+    /// Base color, written without changing the alpha of the material
+    pub base_color: LinearRgba,
+    pub emissive: LinearRgba,
+    /// Translation of the base color texture coordinates
+    pub uv_offset: Vec2,
+    /// Scale of the base color texture coordinates
+    pub uv_scale: Vec2,
+    /// Rotation of the base color texture coordinates, in radians
+    pub uv_rotation: f32,
+    /// Properties with an animation track, the only ones copied into the
+    /// material
+    #[reflect(ignore)]
+    pub animated: Vec<FbxMaterialProperty>,
+}
+
+impl FbxMaterialAnimation {
+    /// Values of a material as created by [`create_standard_material`],
+    /// with no animated properties.
+    pub fn from_material(material: &ufbx::Material) -> Self {
+        let xyz = |v: ufbx::Vec4| Vec3::new(v.x as f32, v.y as f32, v.z as f32);
+        let diffuse = xyz(material.fbx.diffuse_color.value_vec4);
+        let emission = xyz(material.fbx.emission_color.value_vec4);
+        let (uv_scale, uv_rotation, uv_offset) = base_color_texture(material)
+            .map_or((Vec2::ONE, 0.0, Vec2::ZERO), |texture| {
+                convert_texture_uv_parts(texture)
+            });
+
+        Self {
+            base_color: LinearRgba::from_vec3(convert_material_value(
+                FbxMaterialProperty::BaseColor,
+                diffuse,
+            )),
+            emissive: LinearRgba::from_vec3(emission),
+            uv_offset,
+            uv_scale,
+            uv_rotation,
+            animated: Vec::new(),
+        }
+    }
+
+    /// Whether a property has an animation track.
+    pub fn is_animated(&self, property: FbxMaterialProperty) -> bool {
+        self.animated.contains(&property)
+    }
+
+    /// UV transform of the base color texture.
+    pub fn uv_transform(&self) -> Affine2 {
+        Affine2::from_scale_angle_translation(self.uv_scale, self.uv_rotation, self.uv_offset)
+    }
+}
+
+/// Copy changed material animation values into the materials of their
+/// entities.
+pub fn apply_fbx_material_animation(
+    animated: Query<
+        (&FbxMaterialAnimation, &MeshMaterial3d<StandardMaterial>),
+        Changed<FbxMaterialAnimation>,
+    >,
+    materials: Option<ResMut<Assets<StandardMaterial>>>,
+) {
+    // Materials only exist with the PBR plugin
+    let Some(mut materials) = materials else {
+        return;
+    };
+    for (animation, material) in &animated {
+        let Some(mut standard_material) = materials.get_mut(&material.0) else {
+            continue;
+        };
+        if animation.is_animated(FbxMaterialProperty::BaseColor) {
+            let alpha = standard_material.base_color.alpha();
+            standard_material.base_color =
+                Color::LinearRgba(animation.base_color.with_alpha(alpha));
+        }
+        if animation.is_animated(FbxMaterialProperty::EmissiveColor) {
+            standard_material.emissive = animation.emissive;
+        }
+        if animation.is_animated(FbxMaterialProperty::UvOffset)
+            || animation.is_animated(FbxMaterialProperty::UvScale)
+            || animation.is_animated(FbxMaterialProperty::UvRotation)
+        {
+            standard_material.uv_transform = animation.uv_transform();
+        }
+    }
+}
@@ -2,9 +2,15 @@
 
 use crate::error::FbxError;
 use crate::label::FbxAssetLabel;
-use crate::loader::FbxLoaderSettings;
-use bevy::asset::{Handle, LoadContext};
+use crate::loader::{FbxLoaderSettings, FbxSkinningSettings};
+use crate::node::node_name;
+use crate::types::FbxSkinningWarning;
+use crate::utils::{convert_matrix, convert_skinning_method};
+use bevy::asset::{Handle, LoadContext, RenderAssetUsages};
+use bevy::pbr::MAX_JOINTS;
 use bevy::prelude::*;
+use bevy::mesh::morph::MorphAttributes;
+use bevy::mesh::skinning::SkinnedMeshInverseBindposes;
 use bevy::mesh::{Indices, PrimitiveTopology, VertexAttributeValues};
 use std::collections::HashMap;
 
@@ -17,15 +23,19 @@ pub fn process_meshes(
     (
         Vec<Handle<Mesh>>,
         HashMap<Box<str>, Handle<Mesh>>,
-        Vec<ufbx::Matrix>,
+        Vec<usize>,
         Vec<Vec<String>>,
+        Vec<Option<FbxJointPalette>>,
+        Vec<FbxSkinningWarning>,
     ),
     FbxError,
 > {
     let mut meshes = Vec::new();
     let mut named_meshes = HashMap::new();
-    let mut transforms = Vec::new();
+    let mut mesh_nodes = Vec::new();
     let mut mesh_material_info = Vec::new();
+    let mut joint_palettes = Vec::new();
+    let mut warnings = Vec::new();
 
     for (index, node) in scene.nodes.as_ref().iter().enumerate() {
         let Some(mesh_ref) = node.mesh.as_ref() else {
@@ -40,13 +50,27 @@ pub fn process_meshes(
         // Group faces by material
         let material_groups = group_faces_by_material(mesh);
 
+        // Morph targets and skin weights are shared by every material group
+        // of the mesh
+        let vertex_data = FbxMeshVertexData {
+            morph_targets: create_morph_targets(mesh),
+            skin_weights: process_skinning_data(mesh, &settings.skinning),
+        };
+        if let Some(skin_weights) = &vertex_data.skin_weights {
+            for warning in skinning_warnings(mesh, &node_name(node), skin_weights) {
+                warn!("{}", warning);
+                warnings.push(warning);
+            }
+        }
+
         // Create mesh for each material group
         for (material_idx, indices) in material_groups.iter() {
-            let mesh_handle = create_mesh_from_group(
+            let (mesh_handle, joint_palette) = create_mesh_from_group(
                 mesh,
                 indices,
                 index,
                 *material_idx,
+                &vertex_data,
                 settings,
                 load_context,
             )?;
@@ -56,7 +80,8 @@ pub fn process_meshes(
             }
 
             meshes.push(mesh_handle);
-            transforms.push(node.geometry_to_world);
+            mesh_nodes.push(index);
+            joint_palettes.push(joint_palette);
 
             let material_name = if *material_idx < mesh.materials.len() {
                 mesh.materials[*material_idx].element.name.to_string()
@@ -67,7 +92,14 @@ pub fn process_meshes(
         }
     }
 
-    Ok((meshes, named_meshes, transforms, mesh_material_info))
+    Ok((
+        meshes,
+        named_meshes,
+        mesh_nodes,
+        mesh_material_info,
+        joint_palettes,
+        warnings,
+    ))
 }
 
 /// Group mesh faces by material index.
@@ -113,19 +145,70 @@ pub fn group_faces_by_material(mesh: &ufbx::Mesh) -> HashMap<usize, Vec<u32>> {
     material_groups
 }
 
+/// Vertex data of an FBX mesh shared by the meshes of its material groups.
+#[derive(Default)]
+pub struct FbxMeshVertexData {
+    pub morph_targets: Option<(Vec<MorphAttributes>, Vec<String>)>,
+    pub skin_weights: Option<FbxSkinWeights>,
+}
+
+/// Joints of a mesh generated for one material group of a skinned mesh.
+///
+/// The joint indices of the mesh index into `joints`, which holds the indices
+/// into [`skin_clusters`] of the clusters in use by the faces of the group.
+#[derive(Debug, Clone)]
+pub struct FbxJointPalette {
+    pub joints: Vec<u16>,
+    /// Inverse bind pose of each joint of the palette
+    pub inverse_bindposes: Handle<SkinnedMeshInverseBindposes>,
+}
+
 /// Create a Bevy mesh from a material group.
+///
+/// Skinned meshes also get the joint palette their joint indices refer to.
 pub fn create_mesh_from_group(
     ufbx_mesh: &ufbx::Mesh,
     indices: &[u32],
     mesh_index: usize,
     material_index: usize,
+    vertex_data: &FbxMeshVertexData,
     settings: &FbxLoaderSettings,
     load_context: &mut LoadContext,
-) -> Result<Handle<Mesh>, FbxError> {
+) -> Result<(Handle<Mesh>, Option<FbxJointPalette>), FbxError> {
     let label = FbxAssetLabel::Mesh(mesh_index * 1000 + material_index).to_string();
 
+    // Only the joints used by the faces of the group are bound
+    let skinning = vertex_data.skin_weights.as_ref().map(|skin_weights| {
+        let (joints, joint_indices) = compact_joint_palette(skin_weights, indices);
+        if joints.len() > MAX_JOINTS {
+            warn!(
+                "Mesh {} uses {} joints, more than the {} supported per draw",
+                label,
+                joints.len(),
+                MAX_JOINTS
+            );
+        }
+        let inverse_bindposes = joint_inverse_bindposes(ufbx_mesh, &joints);
+        let joint_palette = FbxJointPalette {
+            joints,
+            inverse_bindposes: load_context.add_labeled_asset(
+                format!("{}_InverseBindposes", label),
+                SkinnedMeshInverseBindposes::from(inverse_bindposes),
+            ),
+        };
+        (joint_palette, joint_indices)
+    });
+    let (joint_palette, joint_indices) = skinning.unzip();
+
     let handle = load_context.labeled_asset_scope(label, |_| {
-        let mut bevy_mesh = Mesh::new(PrimitiveTopology::TriangleList, settings.load_meshes);
+        // Positions of meshes deformed by geometry caches are rewritten by
+        // their vertex animation, so they stay in the main world
+        let asset_usage = if ufbx_mesh.cache_deformers.is_empty() {
+            settings.load_meshes
+        } else {
+            settings.load_meshes | RenderAssetUsages::MAIN_WORLD
+        };
+        let mut bevy_mesh = Mesh::new(PrimitiveTopology::TriangleList, asset_usage);
 
         // Positions
         let positions: Vec<[f32; 3]> = ufbx_mesh
@@ -160,8 +243,23 @@ pub fn create_mesh_from_group(
         }
 
         // Skinning
-        if !ufbx_mesh.skin_deformers.is_empty() {
-            process_skinning_data(ufbx_mesh, &mut bevy_mesh);
+        if let Some(skin_weights) = &vertex_data.skin_weights
+            && let Some(joint_indices) = joint_indices
+        {
+            bevy_mesh.insert_attribute(
+                Mesh::ATTRIBUTE_JOINT_INDEX,
+                VertexAttributeValues::Uint16x4(joint_indices),
+            );
+            bevy_mesh.insert_attribute(
+                Mesh::ATTRIBUTE_JOINT_WEIGHT,
+                skin_weights.joint_weights.clone(),
+            );
+        }
+
+        // Morph targets
+        if let Some((morph_target_attributes, morph_target_names)) = &vertex_data.morph_targets {
+            bevy_mesh.set_morph_targets(morph_target_attributes.clone());
+            bevy_mesh.set_morph_target_names(morph_target_names.clone());
         }
 
         // Indices
@@ -170,49 +268,396 @@ pub fn create_mesh_from_group(
         Ok::<_, FbxError>(bevy_mesh)
     })?;
 
-    Ok(handle)
+    Ok((handle, joint_palette))
 }
 
-/// Process skinning data for a mesh.
-pub fn process_skinning_data(ufbx_mesh: &ufbx::Mesh, bevy_mesh: &mut Mesh) {
-    let skin_deformer = &ufbx_mesh.skin_deformers[0];
-    let mut joint_indices = vec![[0u16; 4]; ufbx_mesh.num_vertices];
-    let mut joint_weights = vec![[0.0f32; 4]; ufbx_mesh.num_vertices];
+/// Remap the joint indices of the vertices used by `indices` to a palette of
+/// the joints they use.
+///
+/// Returns the palette, as cluster indices in ascending order, and the joint
+/// indices into it of every vertex. Unused vertices and influences without
+/// weight point at the first joint.
+pub fn compact_joint_palette(
+    skin_weights: &FbxSkinWeights,
+    indices: &[u32],
+) -> (Vec<u16>, Vec<[u16; 4]>) {
+    let mut used = vec![false; skin_weights.joint_indices.len()];
+    for &index in indices {
+        if let Some(used) = used.get_mut(index as usize) {
+            *used = true;
+        }
+    }
 
-    for vertex_index in 0..ufbx_mesh.num_vertices {
-        let mut weight_count = 0;
-        let mut total_weight = 0.0f32;
+    let influences = |vertex: usize| {
+        skin_weights.joint_indices[vertex]
+            .into_iter()
+            .zip(skin_weights.joint_weights[vertex])
+            .filter(|(_, weight)| *weight > 0.0)
+            .map(|(joint, _)| joint)
+    };
 
-        for (cluster_index, cluster) in skin_deformer.clusters.iter().enumerate() {
-            if weight_count >= 4 {
-                break;
-            }
+    let mut joints: Vec<u16> = (0..used.len())
+        .filter(|&vertex| used[vertex])
+        .flat_map(influences)
+        .collect();
+    joints.sort_unstable();
+    joints.dedup();
 
-            for (i, &vert_idx) in cluster.vertices.iter().enumerate() {
-                if vert_idx as usize == vertex_index && i < cluster.weights.len() {
-                    let weight = cluster.weights[i] as f32;
+    let joint_indices = (0..used.len())
+        .map(|vertex| {
+            let mut remapped = [0u16; 4];
+            if used[vertex] {
+                for (slot, (joint, weight)) in skin_weights.joint_indices[vertex]
+                    .into_iter()
+                    .zip(skin_weights.joint_weights[vertex])
+                    .enumerate()
+                {
                     if weight > 0.0 {
-                        joint_indices[vertex_index][weight_count] = cluster_index as u16;
-                        joint_weights[vertex_index][weight_count] = weight;
-                        total_weight += weight;
-                        weight_count += 1;
-                        break;
+                        remapped[slot] = joints.binary_search(&joint).unwrap_or(0) as u16;
                     }
                 }
             }
+            remapped
+        })
+        .collect();
+
+    (joints, joint_indices)
+}
+
+/// Inverse bind poses of a palette of joints of the skin deformers.
+///
+/// Mesh vertices are in geometry space, so they are bound from there rather
+/// than from world space.
+fn joint_inverse_bindposes(ufbx_mesh: &ufbx::Mesh, joints: &[u16]) -> Vec<Mat4> {
+    let clusters = skin_clusters(ufbx_mesh);
+    joints
+        .iter()
+        .map(|&joint| {
+            clusters
+                .get(joint as usize)
+                .map_or(Mat4::IDENTITY, |cluster| {
+                    convert_matrix(&cluster.geometry_to_bone)
+                })
+        })
+        .collect()
+}
+
+/// Create the morph target displacements of a mesh from its blend shapes.
+///
+/// Every shape of every blend channel becomes one morph target, so in-between
+/// shapes are driven through [`morph_target_weights`]. Displacements are
+/// stored target after target, each covering every vertex.
+pub fn create_morph_targets(ufbx_mesh: &ufbx::Mesh) -> Option<(Vec<MorphAttributes>, Vec<String>)> {
+    let vertex_count = ufbx_mesh.vertex_position.values.len();
+    let mut targets = Vec::new();
+    let mut names = Vec::new();
+
+    for deformer in ufbx_mesh.blend_deformers.iter() {
+        for channel in deformer.channels.iter() {
+            for (keyframe_index, keyframe) in channel.keyframes.iter().enumerate() {
+                let shape = &keyframe.shape;
+                let mut attributes = vec![MorphAttributes::default(); vertex_count];
+
+                for (offset_index, &vertex) in shape.offset_vertices.iter().enumerate() {
+                    let Some(target) = attributes.get_mut(vertex as usize) else {
+                        continue;
+                    };
+                    let weight = shape
+                        .offset_weights
+                        .get(offset_index)
+                        .map_or(1.0, |&w| w as f32);
+                    let position = shape.position_offsets[offset_index];
+                    target.position =
+                        Vec3::new(position.x as f32, position.y as f32, position.z as f32) * weight;
+                    if let Some(normal) = shape.normal_offsets.get(offset_index) {
+                        target.normal =
+                            Vec3::new(normal.x as f32, normal.y as f32, normal.z as f32) * weight;
+                    }
+                }
+                targets.extend(attributes);
+
+                // The full shape is named after the channel, in-betweens after
+                // the channel weight they are reached at
+                let name = if keyframe_index + 1 == channel.keyframes.len() {
+                    channel.element.name.to_string()
+                } else {
+                    format!(
+                        "{}_{}",
+                        channel.element.name,
+                        (keyframe.target_weight * 100.0).round()
+                    )
+                };
+                names.push(name);
+            }
         }
+    }
+
+    if names.is_empty() {
+        return None;
+    }
+    Some((targets, names))
+}
 
-        // Normalize weights
-        if total_weight > 0.0 {
-            for i in 0..weight_count {
-                joint_weights[vertex_index][i] /= total_weight;
+/// Morph target weights of a mesh for the given blend channel weights.
+///
+/// The weights are ordered like the targets of [`create_morph_targets`].
+pub fn morph_target_weights(
+    ufbx_mesh: &ufbx::Mesh,
+    channel_weight: impl Fn(&ufbx::BlendChannel) -> f32,
+) -> Vec<f32> {
+    let mut weights = Vec::new();
+    for deformer in ufbx_mesh.blend_deformers.iter() {
+        for channel in deformer.channels.iter() {
+            let target_weights: Vec<f32> = channel
+                .keyframes
+                .iter()
+                .map(|keyframe| keyframe.target_weight as f32)
+                .collect();
+            weights.extend(inbetween_weights(&target_weights, channel_weight(channel)));
+        }
+    }
+    weights
+}
+
+/// Split a blend channel weight between the shapes of the channel.
+///
+/// `target_weights` are the ascending channel weights at which each shape is
+/// fully applied. Between two shapes the weight cross-fades from one to the
+/// other, below the first shape it fades in from the base mesh.
+pub fn inbetween_weights(target_weights: &[f32], weight: f32) -> Vec<f32> {
+    let mut weights = vec![0.0; target_weights.len()];
+    if target_weights.is_empty() {
+        return weights;
+    }
+
+    let last = target_weights.len() - 1;
+    match target_weights.iter().position(|&target| weight <= target) {
+        Some(index) if index > 0 => {
+            let (low, high) = (target_weights[index - 1], target_weights[index]);
+            let t = if high > low {
+                (weight - low) / (high - low)
+            } else {
+                1.0
+            };
+            weights[index - 1] = 1.0 - t;
+            weights[index] = t;
+        }
+        Some(_) if target_weights[0] > 0.0 => {
+            weights[0] = weight / target_weights[0];
+        }
+        None if target_weights[last] > 0.0 => {
+            weights[last] = weight / target_weights[last];
+        }
+        _ => {}
+    }
+    weights
+}
+
+/// Joint indices and weights of every vertex of a skinned mesh.
+#[derive(Debug, Clone, Default)]
+pub struct FbxSkinWeights {
+    pub joint_indices: Vec<[u16; 4]>,
+    pub joint_weights: Vec<[f32; 4]>,
+    /// Vertices which lost influences above the minimum weight to the
+    /// influence limit
+    pub limited_vertices: usize,
+    /// Largest weight dropped by the influence limit, before renormalization
+    pub max_dropped_weight: f32,
+    /// Vertices influenced by more than one skin deformer
+    pub overlapping_vertices: usize,
+    /// Vertices without any weight, bound rigidly to a single joint
+    pub unweighted_vertices: usize,
+}
+
+/// Clusters of every skin deformer of a mesh, in the order of the joint
+/// indices of its skin weights.
+pub fn skin_clusters(ufbx_mesh: &ufbx::Mesh) -> Vec<&ufbx::SkinCluster> {
+    ufbx_mesh
+        .skin_deformers
+        .as_ref()
+        .iter()
+        .flat_map(|skin_deformer| skin_deformer.clusters.as_ref().iter())
+        .map(|cluster| cluster.as_ref())
+        .collect()
+}
+
+/// Process skinning data for a mesh.
+///
+/// Influences are read from the per-vertex weight tables of the skin
+/// deformers, so the cost is linear in the number of weights. Deformers are
+/// merged into one weight set, with the clusters of each following those of
+/// the previous ones, see [`skin_clusters`]. Each vertex keeps its largest
+/// influences within the limits of `settings`.
+pub fn process_skinning_data(
+    ufbx_mesh: &ufbx::Mesh,
+    settings: &FbxSkinningSettings,
+) -> Option<FbxSkinWeights> {
+    let skin_deformers = ufbx_mesh.skin_deformers.as_ref();
+    if skin_deformers.is_empty() {
+        return None;
+    }
+    let mut skin_weights = FbxSkinWeights {
+        joint_indices: Vec::with_capacity(ufbx_mesh.num_vertices),
+        joint_weights: Vec::with_capacity(ufbx_mesh.num_vertices),
+        ..Default::default()
+    };
+
+    let mut influences = Vec::new();
+    for vertex_index in 0..ufbx_mesh.num_vertices {
+        influences.clear();
+        let mut cluster_offset = 0;
+        let mut deformers = 0;
+        for skin_deformer in skin_deformers {
+            let weights = skin_deformer.weights.as_ref();
+            if let Some(vertex) = skin_deformer.vertices.as_ref().get(vertex_index)
+                && vertex.num_weights > 0
+            {
+                let begin = vertex.weight_begin as usize;
+                let end = (begin + vertex.num_weights as usize).min(weights.len());
+                influences.extend(
+                    weights[begin.min(end)..end].iter().map(|weight| {
+                        (cluster_offset + weight.cluster_index, weight.weight as f32)
+                    }),
+                );
+                deformers += 1;
             }
+            cluster_offset += skin_deformer.clusters.len() as u32;
+        }
+        if deformers > 1 {
+            skin_weights.overlapping_vertices += 1;
+        }
+        if influences.iter().all(|(_, weight)| *weight <= 0.0) {
+            skin_weights.unweighted_vertices += 1;
+        }
+
+        let (joint_indices, joint_weights, dropped_weight) = limit_influences(
+            &mut influences,
+            settings.max_influences,
+            settings.min_weight,
+        );
+        if dropped_weight > 0.0 {
+            skin_weights.limited_vertices += 1;
+            skin_weights.max_dropped_weight = skin_weights.max_dropped_weight.max(dropped_weight);
         }
+        skin_weights.joint_indices.push(joint_indices);
+        skin_weights.joint_weights.push(joint_weights);
     }
 
-    bevy_mesh.insert_attribute(
-        Mesh::ATTRIBUTE_JOINT_INDEX,
-        VertexAttributeValues::Uint16x4(joint_indices),
+    Some(skin_weights)
+}
+
+/// Skinning of a mesh which its skin weights do not reproduce exactly.
+///
+/// Bevy only supports linear blend skinning, so dual quaternion skinning is
+/// reported, as is blended skinning with any dual quaternion weight.
+pub fn skinning_warnings(
+    ufbx_mesh: &ufbx::Mesh,
+    mesh_name: &str,
+    skin_weights: &FbxSkinWeights,
+) -> Vec<FbxSkinningWarning> {
+    let mut warnings = Vec::new();
+
+    if skin_weights.limited_vertices > 0 {
+        warnings.push(FbxSkinningWarning::InfluencesDropped {
+            mesh: mesh_name.to_string(),
+            vertices: skin_weights.limited_vertices,
+            max_dropped_weight: skin_weights.max_dropped_weight,
+        });
+    }
+    if skin_weights.overlapping_vertices > 0 {
+        warnings.push(FbxSkinningWarning::OverlappingDeformers {
+            mesh: mesh_name.to_string(),
+            deformers: ufbx_mesh.skin_deformers.len(),
+            vertices: skin_weights.overlapping_vertices,
+        });
+    }
+
+    let unbound_clusters = skin_clusters(ufbx_mesh)
+        .iter()
+        .filter(|cluster| cluster.bone_node.is_none())
+        .count();
+    if unbound_clusters > 0 {
+        warnings.push(FbxSkinningWarning::UnboundClusters {
+            mesh: mesh_name.to_string(),
+            clusters: unbound_clusters,
+        });
+    }
+    if skin_weights.unweighted_vertices > 0 {
+        warnings.push(FbxSkinningWarning::UnweightedVertices {
+            mesh: mesh_name.to_string(),
+            vertices: skin_weights.unweighted_vertices,
+        });
+    }
+
+    let mut methods = Vec::new();
+    for skin_deformer in ufbx_mesh.skin_deformers.as_ref() {
+        let unsupported = match skin_deformer.skinning_method {
+            ufbx::SkinningMethod::Linear | ufbx::SkinningMethod::Rigid => false,
+            ufbx::SkinningMethod::DualQuaternion => true,
+            ufbx::SkinningMethod::BlendedDqLinear => skin_deformer
+                .vertices
+                .as_ref()
+                .iter()
+                .any(|vertex| vertex.dq_weight > 0.0),
+        };
+        let method = convert_skinning_method(skin_deformer.skinning_method);
+        if unsupported && !methods.contains(&method) {
+            methods.push(method);
+        }
+    }
+    warnings.extend(
+        methods
+            .into_iter()
+            .map(|method| FbxSkinningWarning::UnsupportedMethod {
+                mesh: mesh_name.to_string(),
+                method,
+            }),
     );
-    bevy_mesh.insert_attribute(Mesh::ATTRIBUTE_JOINT_WEIGHT, joint_weights);
+
+    warnings
+}
+
+/// Keep the largest influences of a vertex, renormalized to sum to one.
+///
+/// Influences of `(joint, weight)` at or below `min_weight` are pruned, unless
+/// that would leave the vertex without any, then the largest
+/// `max_influences` (at most four) are kept. A vertex without any weight is
+/// bound rigidly to joint 0, as Bevy would otherwise collapse it to the
+/// origin. Returns the joint indices and weights, along with the largest
+/// weight dropped by the influence limit.
+pub fn limit_influences(
+    influences: &mut Vec<(u32, f32)>,
+    max_influences: usize,
+    min_weight: f32,
+) -> ([u16; 4], [f32; 4], f32) {
+    let limit = max_influences.clamp(1, 4);
+
+    influences.sort_unstable_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    let kept = match influences
+        .iter()
+        .take_while(|(_, weight)| *weight > min_weight)
+        .count()
+    {
+        0 => influences.len().min(limit),
+        kept => kept,
+    };
+    influences.truncate(kept);
+
+    let dropped_weight = influences.get(limit).map_or(0.0, |(_, weight)| *weight);
+    influences.truncate(limit);
+
+    let mut joint_indices = [0u16; 4];
+    let mut joint_weights = [0.0f32; 4];
+    let total_weight: f32 = influences.iter().map(|(_, weight)| weight).sum();
+    if total_weight <= 0.0 {
+        joint_indices[0] = influences.first().map_or(0, |(joint, _)| *joint as u16);
+        joint_weights[0] = 1.0;
+        return (joint_indices, joint_weights, dropped_weight);
+    }
+    for (slot, (joint, weight)) in influences.iter().enumerate() {
+        joint_indices[slot] = *joint as u16;
+        joint_weights[slot] = weight / total_weight;
+    }
+
+    (joint_indices, joint_weights, dropped_weight)
 }
@@ -1,12 +1,15 @@
 //! Node and hierarchy processing for FBX files.
 
 use crate::error::FbxError;
+use crate::humanoid::create_humanoid;
 use crate::label::FbxAssetLabel;
-use crate::types::{FbxNode, FbxSkin};
+use crate::mesh::skin_clusters;
+use crate::types::{FbxHumanoid, FbxJointKind, FbxNode, FbxSkin, Skeleton};
 use crate::utils::{convert_matrix, convert_transform};
+use bevy::animation::AnimationTargetId;
 use bevy::asset::{Handle, LoadContext};
-use bevy::prelude::*;
 use bevy::mesh::skinning::SkinnedMeshInverseBindposes;
+use bevy::prelude::*;
 use std::collections::HashMap;
 
 /// Process nodes and build hierarchy.
@@ -28,11 +31,7 @@ pub fn process_nodes(
 
     // First pass: create nodes
     for (index, ufbx_node) in scene.nodes.as_ref().iter().enumerate() {
-        let name = if ufbx_node.element.name.is_empty() {
-            format!("Node_{}", index)
-        } else {
-            ufbx_node.element.name.to_string()
-        };
+        let name = node_name(ufbx_node);
 
         let mesh_handle = if ufbx_node.mesh.is_some() && index < meshes.len() {
             Some(meshes[index].clone())
@@ -68,10 +67,199 @@ pub fn process_nodes(
     Ok((nodes, named_nodes, node_map))
 }
 
+/// Name of a node, falling back to `Node_{index}` for unnamed nodes.
+pub fn node_name(node: &ufbx::Node) -> String {
+    if node.element.name.is_empty() {
+        format!("Node_{}", node.element.typed_id)
+    } else {
+        node.element.name.to_string()
+    }
+}
+
+/// Compute the animation target id of a node from the names on its path
+/// below the scene root.
+pub fn animation_target_id(node: &ufbx::Node) -> AnimationTargetId {
+    let mut path = Vec::new();
+    let mut current = Some(node);
+    while let Some(n) = current {
+        if n.is_root {
+            break;
+        }
+        path.push(Name::new(node_name(n)));
+        current = n.parent.as_deref();
+    }
+    path.reverse();
+    AnimationTargetId::from_names(path.iter())
+}
+
+/// Skeleton and humanoid of each joint, by node index.
+pub type JointSkeletons = HashMap<usize, (Handle<Skeleton>, Option<Handle<FbxHumanoid>>)>;
+
+/// Add the skeletons of a scene as labeled assets.
+///
+/// Skeletons whose joints follow a known humanoid naming scheme also get an
+/// [`FbxHumanoid`] map. Returns the skeleton and humanoid handles, along with
+/// the skeleton of each joint and its humanoid by node index.
+pub fn process_skeletons(
+    scene: &ufbx::Scene,
+    nodes: &[Handle<FbxNode>],
+    load_context: &mut LoadContext,
+) -> (
+    Vec<Handle<Skeleton>>,
+    Vec<Handle<FbxHumanoid>>,
+    JointSkeletons,
+) {
+    let mut skeletons = Vec::new();
+    let mut humanoids = Vec::new();
+    let mut joint_skeletons = HashMap::new();
+
+    for mut skeleton in find_skeletons(scene) {
+        let joints: Vec<_> = skeleton
+            .nodes
+            .iter()
+            .map(|&index| (&scene.nodes[index], nodes[index].clone()))
+            .collect();
+        skeleton.humanoid = create_humanoid(&joints).map(|humanoid| {
+            load_context.add_labeled_asset(
+                FbxAssetLabel::Humanoid(skeleton.index).to_string(),
+                humanoid,
+            )
+        });
+        humanoids.extend(skeleton.humanoid.clone());
+
+        let joint_nodes = skeleton.nodes.clone();
+        let humanoid = skeleton.humanoid.clone();
+        let handle = load_context.add_labeled_asset(
+            FbxAssetLabel::Skeleton(skeleton.index).to_string(),
+            skeleton,
+        );
+        for node in joint_nodes {
+            joint_skeletons.insert(node, (handle.clone(), humanoid.clone()));
+        }
+        skeletons.push(handle);
+    }
+
+    (skeletons, humanoids, joint_skeletons)
+}
+
+/// Find the skeletons of a scene, one per chain of joints under a root bone.
+///
+/// Joints are the `LimbNode` bones, the nodes skin clusters bind to, and the
+/// nodes between two of those, such as null groups inside a rig.
+pub fn find_skeletons(scene: &ufbx::Scene) -> Vec<Skeleton> {
+    let nodes = scene.nodes.as_ref();
+
+    let mut bind_matrices = vec![None; nodes.len()];
+    for skin_deformer in scene.skin_deformers.as_ref().iter() {
+        for cluster in skin_deformer.clusters.as_ref().iter() {
+            if let Some(bone_node) = cluster.bone_node.as_ref() {
+                bind_matrices[bone_node.element.typed_id as usize]
+                    .get_or_insert_with(|| convert_matrix(&cluster.bind_to_world));
+            }
+        }
+    }
+
+    let is_bone: Vec<bool> = nodes
+        .iter()
+        .map(|node| {
+            !node.is_root
+                && (node.bone.is_some() || bind_matrices[node.element.typed_id as usize].is_some())
+        })
+        .collect();
+
+    // Nodes between two bones belong to the chain as well
+    let mut is_joint = is_bone.clone();
+    for node in nodes
+        .iter()
+        .filter(|node| is_bone[node.element.typed_id as usize])
+    {
+        let mut between = Vec::new();
+        let mut parent = node.parent.as_ref();
+        while let Some(ancestor) = parent.filter(|ancestor| !ancestor.is_root) {
+            let ancestor_index = ancestor.element.typed_id as usize;
+            if is_bone[ancestor_index] {
+                for index in between.drain(..) {
+                    is_joint[index] = true;
+                }
+                break;
+            }
+            between.push(ancestor_index);
+            parent = ancestor.parent.as_ref();
+        }
+    }
+
+    let mut skeletons = Vec::new();
+    for root in nodes.iter() {
+        let root_index = root.element.typed_id as usize;
+        let parent_is_joint = root
+            .parent
+            .as_ref()
+            .is_some_and(|parent| is_joint[parent.element.typed_id as usize]);
+        if !is_joint[root_index] || parent_is_joint {
+            continue;
+        }
+
+        let mut skeleton = Skeleton {
+            index: skeletons.len(),
+            name: node_name(root),
+            joint_names: Vec::new(),
+            parents: Vec::new(),
+            rest_transforms: Vec::new(),
+            bind_matrices: Vec::new(),
+            kinds: Vec::new(),
+            nodes: Vec::new(),
+            targets: Vec::new(),
+            humanoid: None,
+        };
+
+        // Depth first, so that parents come before their children
+        let mut stack = vec![(root, None)];
+        while let Some((node, parent)) = stack.pop() {
+            let index = node.element.typed_id as usize;
+            let joint = skeleton.nodes.len();
+            skeleton.joint_names.push(node_name(node));
+            skeleton.parents.push(parent);
+            skeleton
+                .rest_transforms
+                .push(convert_transform(&node.local_transform));
+            skeleton
+                .bind_matrices
+                .push(bind_matrices[index].unwrap_or_else(|| convert_matrix(&node.node_to_world)));
+            skeleton.kinds.push(joint_kind(node));
+            skeleton.nodes.push(index);
+            skeleton.targets.push(animation_target_id(node));
+
+            for child in node.children.as_ref().iter().rev() {
+                if is_joint[child.element.typed_id as usize] {
+                    stack.push((child, Some(joint)));
+                }
+            }
+        }
+
+        skeletons.push(skeleton);
+    }
+
+    skeletons
+}
+
+fn joint_kind(node: &ufbx::Node) -> FbxJointKind {
+    if node.bone.is_some() {
+        FbxJointKind::LimbNode
+    } else if node.attrib.is_none() || node.attrib_type == ufbx::ElementType::Empty {
+        FbxJointKind::Null
+    } else {
+        FbxJointKind::Other
+    }
+}
+
 /// Process skins for skeletal animation.
+///
+/// Skins reference the skeleton of their joints, and its [`FbxHumanoid`] map
+/// if it has one.
 pub fn process_skins(
     scene: &ufbx::Scene,
     node_map: &HashMap<u32, Handle<FbxNode>>,
+    skeletons: &JointSkeletons,
     load_context: &mut LoadContext,
 ) -> Result<(Vec<Handle<FbxSkin>>, HashMap<Box<str>, Handle<FbxSkin>>), FbxError> {
     let mut skins = Vec::new();
@@ -87,19 +275,26 @@ pub fn process_skins(
             continue;
         }
 
-        let skin_deformer = &mesh.skin_deformers[0];
         let mut inverse_bind_matrices = Vec::new();
         let mut joint_handles = Vec::new();
 
-        for cluster in &skin_deformer.clusters {
-            let bind_matrix = convert_matrix(&cluster.bind_to_world);
-            inverse_bind_matrices.push(bind_matrix.inverse());
-
-            if let Some(bone_node) = cluster.bone_node.as_ref() {
-                if let Some(joint_handle) = node_map.get(&bone_node.element.element_id) {
-                    joint_handles.push(joint_handle.clone());
-                }
-            }
+        // Clusters of every skin deformer, matching the joint indices of the
+        // skin weights. Clusters without a bone are bound to the skinned node
+        // like in the scene, so that later joints stay aligned, and are
+        // reported as skinning warnings.
+        let Some(skinned_node) = node_map.get(&node.element.element_id) else {
+            continue;
+        };
+        for cluster in skin_clusters(mesh) {
+            let joint_handle = cluster
+                .bone_node
+                .as_ref()
+                .and_then(|bone_node| node_map.get(&bone_node.element.element_id))
+                .unwrap_or(skinned_node);
+            joint_handles.push(joint_handle.clone());
+            // Mesh vertices are in geometry space, so they are bound from
+            // there rather than from world space
+            inverse_bind_matrices.push(convert_matrix(&cluster.geometry_to_bone));
         }
 
         if !inverse_bind_matrices.is_empty() {
@@ -114,11 +309,23 @@ pub fn process_skins(
                 format!("{}_Skin", node.element.name)
             };
 
+            let (skeleton, humanoid) = skin_clusters(mesh)
+                .iter()
+                .find_map(|cluster| {
+                    let bone_node = cluster.bone_node.as_ref()?;
+                    skeletons
+                        .get(&(bone_node.element.typed_id as usize))
+                        .cloned()
+                })
+                .unzip();
+
             let fbx_skin = FbxSkin {
                 index: skin_index,
                 name: skin_name.clone(),
                 joints: joint_handles,
                 inverse_bind_matrices: inverse_bindposes_handle,
+                skeleton,
+                humanoid: humanoid.flatten(),
             };
 
             let handle = load_context
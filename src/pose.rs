@@ -0,0 +1,229 @@
+//! Sampling of node poses from a loaded FBX asset, without an
+//! `AnimationPlayer`.
+
+use crate::animation::sample_keyframes;
+use crate::mesh::morph_target_weights;
+use crate::types::{Fbx, FbxAnimation, FbxMorphTrack};
+use crate::utils::{convert_matrix, convert_transform};
+use bevy::prelude::*;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Transforms and blend shape weights of every node of a scene at one time.
+///
+/// Nodes are indexed like [`Fbx::nodes`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FbxPose {
+    /// Transform of each node relative to its parent
+    pub local: Vec<Transform>,
+    /// Transform of each node in world space, which is Y up and in meters
+    pub world: Vec<Mat4>,
+    /// Morph target weights of the nodes with blend shapes
+    pub morph_weights: HashMap<usize, Vec<f32>>,
+}
+
+/// Node hierarchy and animation curves kept in an [`Fbx`] to sample poses.
+#[derive(Clone, Default)]
+pub struct FbxPoseData {
+    /// Parent of each node
+    pub parents: Vec<Option<usize>>,
+    /// Rest transform of each node relative to its parent
+    pub rest: Vec<Transform>,
+    /// Rest morph target weights of the nodes with blend shapes
+    pub morph_weights: HashMap<usize, Vec<f32>>,
+    /// Converted animation of each stack
+    pub animations: Vec<FbxAnimation>,
+    /// Parsed scene, kept with [`FbxLoaderSettings::include_source`](crate::FbxLoaderSettings::include_source)
+    pub source: Option<Arc<ufbx::SceneRoot>>,
+}
+
+impl core::fmt::Debug for FbxPoseData {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("FbxPoseData")
+            .field("parents", &self.parents)
+            .field("rest", &self.rest)
+            .field("morph_weights", &self.morph_weights)
+            .field("animations", &self.animations)
+            .field("source", &self.source.is_some())
+            .finish()
+    }
+}
+
+impl FbxPoseData {
+    /// Keep the hierarchy of a scene along with the animations of its stacks.
+    pub fn new(scene: &ufbx::Scene, animations: Vec<FbxAnimation>) -> Self {
+        Self {
+            parents: scene
+                .nodes
+                .as_ref()
+                .iter()
+                .map(|node| {
+                    node.parent
+                        .as_ref()
+                        .map(|parent| parent.element.typed_id as usize)
+                })
+                .collect(),
+            rest: scene
+                .nodes
+                .as_ref()
+                .iter()
+                .map(|node| convert_transform(&node.local_transform))
+                .collect(),
+            morph_weights: scene_morph_weights(scene),
+            animations,
+            source: None,
+        }
+    }
+
+    /// Pose of animation stack `stack` at `time` seconds from its start.
+    ///
+    /// The parsed scene is evaluated when it was kept, which includes
+    /// everything ufbx supports. Otherwise the converted curves are sampled,
+    /// which reflect the loader settings, such as root motion being moved out
+    /// of the root joint. Returns `None` if the stack does not exist.
+    pub fn sample(&self, stack: usize, time: f32) -> Option<FbxPose> {
+        match &self.source {
+            Some(source) => evaluate_pose(source, stack, time),
+            None => self.sample_curves(stack, time),
+        }
+    }
+
+    fn sample_curves(&self, stack: usize, time: f32) -> Option<FbxPose> {
+        let animation = self.animations.get(stack)?;
+
+        let mut local = self.rest.clone();
+        for track in &animation.tracks {
+            let Some(transform) = local.get_mut(track.node_index) else {
+                continue;
+            };
+            if let Some(translation) = sample_keyframes(&track.translation, time) {
+                transform.translation = translation;
+            }
+            if let Some(rotation) = sample_keyframes(&track.rotation, time) {
+                transform.rotation = rotation;
+            }
+            if let Some(scale) = sample_keyframes(&track.scale, time) {
+                transform.scale = scale;
+            }
+        }
+
+        let mut morph_weights = self.morph_weights.clone();
+        for track in &animation.morph_tracks {
+            if let Some(weights) = sample_morph_weights(track, time) {
+                morph_weights.insert(track.node_index, weights);
+            }
+        }
+
+        let mut world = vec![None; local.len()];
+        for index in 0..local.len() {
+            world_matrix(index, &self.parents, &local, &mut world);
+        }
+
+        Some(FbxPose {
+            local,
+            world: world.into_iter().flatten().collect(),
+            morph_weights,
+        })
+    }
+}
+
+impl Fbx {
+    /// Pose of animation stack `stack` at `time` seconds from its start.
+    ///
+    /// See [`FbxPoseData::sample`].
+    pub fn sample_pose(&self, stack: usize, time: f32) -> Option<FbxPose> {
+        self.poses.sample(stack, time)
+    }
+}
+
+/// Evaluate a stack of a parsed scene at `time` seconds from its start.
+fn evaluate_pose(source: &ufbx::SceneRoot, stack: usize, time: f32) -> Option<FbxPose> {
+    let stack = source.anim_stacks.as_ref().get(stack)?;
+    let evaluated = match ufbx::evaluate_scene(
+        source,
+        &stack.anim,
+        stack.time_begin + time as f64,
+        Default::default(),
+    ) {
+        Ok(evaluated) => evaluated,
+        Err(e) => {
+            warn!(
+                "Failed to evaluate animation stack '{}': {:?}",
+                stack.element.name, e
+            );
+            return None;
+        }
+    };
+
+    let nodes = evaluated.nodes.as_ref();
+    Some(FbxPose {
+        local: nodes
+            .iter()
+            .map(|node| convert_transform(&node.local_transform))
+            .collect(),
+        world: nodes
+            .iter()
+            .map(|node| convert_matrix(&node.node_to_world))
+            .collect(),
+        morph_weights: scene_morph_weights(&evaluated),
+    })
+}
+
+/// Current morph target weights of the nodes with blend shapes.
+fn scene_morph_weights(scene: &ufbx::Scene) -> HashMap<usize, Vec<f32>> {
+    scene
+        .nodes
+        .as_ref()
+        .iter()
+        .enumerate()
+        .filter_map(|(index, node)| {
+            let weights =
+                morph_target_weights(node.mesh.as_ref()?, |channel| channel.weight as f32);
+            (!weights.is_empty()).then_some((index, weights))
+        })
+        .collect()
+}
+
+/// Sample the weights of a morph track, interpolated linearly between keys.
+fn sample_morph_weights(track: &FbxMorphTrack, time: f32) -> Option<Vec<f32>> {
+    let count = track.weights.len().checked_div(track.times.len())?;
+    let keys: Vec<&[f32]> = track.weights.chunks(count.max(1)).collect();
+
+    let next = track.times.partition_point(|&t| t <= time);
+    if next == 0 || next >= keys.len() {
+        let index = next.min(keys.len()).saturating_sub(1);
+        return keys.get(index).map(|weights| weights.to_vec());
+    }
+
+    let (start, end) = (track.times[next - 1], track.times[next]);
+    let t = if end > start {
+        (time - start) / (end - start)
+    } else {
+        1.0
+    };
+    Some(
+        keys[next - 1]
+            .iter()
+            .zip(keys[next])
+            .map(|(a, b)| a + (b - a) * t)
+            .collect(),
+    )
+}
+
+/// Transform of a node in world space, resolving its parents first.
+fn world_matrix(
+    index: usize,
+    parents: &[Option<usize>],
+    local: &[Transform],
+    world: &mut [Option<Mat4>],
+) -> Mat4 {
+    if let Some(matrix) = world[index] {
+        return matrix;
+    }
+    let matrix = match parents.get(index).copied().flatten() {
+        Some(parent) => world_matrix(parent, parents, local, world) * local[index].to_matrix(),
+        None => local[index].to_matrix(),
+    };
+    world[index] = Some(matrix);
+    matrix
+}
@@ -0,0 +1,584 @@
+//! Animated visibility, custom, camera and light properties of FBX nodes.
+
+use crate::node::animation_target_id;
+use crate::types::{
+    FbxAnimatedProperty, FbxInterpolation, FbxKeyframes, FbxLightType, FbxPropertyTrack,
+};
+use crate::utils::convert_vec3;
+use bevy::animation::animation_curves::{
+    AnimatableCurve, AnimatableKeyframeCurve, AnimatableProperty, EvaluatorId,
+};
+use bevy::animation::gltf_curves::SteppedKeyframeCurve;
+use bevy::animation::{
+    animated_field, AnimationEntityMut, AnimationEvaluationError, VariableCurve,
+};
+use bevy::color::ColorToComponents;
+use bevy::ecs::component::Mutable;
+use bevy::math::curve::{ConstantCurve, Interval, UnevenSampleAutoCurve};
+use bevy::platform::hash::Hashed;
+use bevy::prelude::*;
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::marker::PhantomData;
+
+/// Millimetres per inch, the unit of FBX camera apertures.
+const MM_PER_INCH: f64 = 25.4;
+
+/// Bake the animated non-transform properties of a node, its camera and its
+/// light into tracks.
+///
+/// Values are converted to the units of the Bevy components the scene spawns
+/// for the node.
+pub fn bake_property_tracks(
+    node: &ufbx::Node,
+    baked_elements: &HashMap<u32, &ufbx::BakedElement>,
+) -> Vec<FbxPropertyTrack> {
+    let mut tracks = Vec::new();
+
+    for prop in baked_props(baked_elements, &node.element) {
+        let name = prop.name.as_ref();
+        let property = if name == "Visibility" {
+            FbxAnimatedProperty::Visibility
+        } else if is_numeric_user_property(node, name) {
+            FbxAnimatedProperty::UserProperty(name.to_string())
+        } else {
+            continue;
+        };
+        tracks.extend(property_track(node, property, &prop.keys, |value| {
+            Some(scalar(value.x))
+        }));
+    }
+
+    if let Some(camera) = node.camera.as_ref() {
+        for prop in baked_props(baked_elements, &camera.element) {
+            tracks.extend(property_track(
+                node,
+                FbxAnimatedProperty::FieldOfView,
+                &prop.keys,
+                |value| vertical_fov(camera, prop.name.as_ref(), value.x as f64).map(scalar),
+            ));
+        }
+    }
+
+    if let Some(light) = node.light.as_ref() {
+        let Some(light_type) = animated_light_type(light.type_) else {
+            return tracks;
+        };
+        let spot = light_type == FbxLightType::Spot;
+        for prop in baked_props(baked_elements, &light.element) {
+            let track = match prop.name.as_ref() {
+                // FBX intensities are percentages
+                "Intensity" => property_track(
+                    node,
+                    FbxAnimatedProperty::LightIntensity(light_type),
+                    &prop.keys,
+                    |value| Some(scalar(light_intensity(light_type, value.x / 100.0))),
+                ),
+                "Color" => property_track(
+                    node,
+                    FbxAnimatedProperty::LightColor(light_type),
+                    &prop.keys,
+                    |value| Some(light_color(value).to_linear().to_vec3()),
+                ),
+                "InnerAngle" if spot => property_track(
+                    node,
+                    FbxAnimatedProperty::SpotInnerAngle,
+                    &prop.keys,
+                    |value| Some(scalar(spot_angle(value.x))),
+                ),
+                "OuterAngle" | "ConeAngle" if spot => property_track(
+                    node,
+                    FbxAnimatedProperty::SpotOuterAngle,
+                    &prop.keys,
+                    |value| Some(scalar(spot_angle(value.x))),
+                ),
+                _ => None,
+            };
+            tracks.extend(track);
+        }
+    }
+
+    tracks
+}
+
+/// Baked properties of an element, if it is animated.
+fn baked_props<'a>(
+    baked_elements: &HashMap<u32, &'a ufbx::BakedElement>,
+    element: &ufbx::Element,
+) -> impl Iterator<Item = &'a ufbx::BakedProp> {
+    baked_elements
+        .get(&element.element_id)
+        .copied()
+        .into_iter()
+        .flat_map(|baked| baked.props.iter())
+}
+
+/// Convert baked keys into a property track, if every key converts.
+fn property_track(
+    node: &ufbx::Node,
+    property: FbxAnimatedProperty,
+    keys: &[ufbx::BakedVec3],
+    convert: impl Fn(Vec3) -> Option<Vec3>,
+) -> Option<FbxPropertyTrack> {
+    let values = keys
+        .iter()
+        .map(|key| convert(convert_vec3(&key.value)))
+        .collect::<Option<Vec<_>>>()?;
+    Some(FbxPropertyTrack {
+        node_index: node.element.typed_id as usize,
+        target: animation_target_id(node),
+        property,
+        keyframes: FbxKeyframes {
+            times: keys.iter().map(|key| key.time as f32).collect(),
+            values,
+            ..Default::default()
+        },
+    })
+}
+
+/// Whether a property of a node is a user-defined number, integer or boolean.
+fn is_numeric_user_property(node: &ufbx::Node, name: &str) -> bool {
+    node.element.props.find_prop(name).is_some_and(|prop| {
+        prop.flags.has_all(ufbx::PropFlags::USER_DEFINED)
+            && matches!(
+                prop.type_,
+                ufbx::PropType::Boolean | ufbx::PropType::Integer | ufbx::PropType::Number
+            )
+    })
+}
+
+/// Numeric user-defined properties of a node with their values in the file.
+pub fn user_properties(node: &ufbx::Node) -> FbxUserProperties {
+    let values = node
+        .element
+        .props
+        .props
+        .as_ref()
+        .iter()
+        .filter(|prop| is_numeric_user_property(node, prop.name.as_ref()))
+        .map(|prop| (prop.name.to_string(), prop.value_vec4.x as f32))
+        .collect();
+    FbxUserProperties { values }
+}
+
+/// Value of a property in the pose stored in the file, in the units of its
+/// track.
+pub fn rest_property_value(node: &ufbx::Node, property: &FbxAnimatedProperty) -> Option<Vec3> {
+    match property {
+        FbxAnimatedProperty::Visibility => {
+            return Some(scalar(if node.visible { 1.0 } else { 0.0 }));
+        }
+        FbxAnimatedProperty::UserProperty(name) => {
+            let value = node
+                .element
+                .props
+                .find_prop(name)
+                .map_or(0.0, |prop| prop.value_vec4.x);
+            return Some(scalar(value as f32));
+        }
+        FbxAnimatedProperty::FieldOfView => {
+            let camera = node.camera.as_ref()?;
+            return Some(scalar(camera.field_of_view_deg.y.to_radians() as f32));
+        }
+        _ => {}
+    }
+
+    let light = node.light.as_ref()?;
+    let value = match property {
+        FbxAnimatedProperty::LightIntensity(light_type) => {
+            scalar(light_intensity(*light_type, light.intensity as f32))
+        }
+        FbxAnimatedProperty::LightColor(_) => light_color(convert_vec3(&light.color))
+            .to_linear()
+            .to_vec3(),
+        FbxAnimatedProperty::SpotInnerAngle => scalar(spot_angle(light.inner_angle as f32)),
+        FbxAnimatedProperty::SpotOuterAngle => scalar(spot_angle(light.outer_angle as f32)),
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// Light types whose Bevy components have animatable properties.
+fn animated_light_type(light_type: ufbx::LightType) -> Option<FbxLightType> {
+    match light_type {
+        ufbx::LightType::Directional => Some(FbxLightType::Directional),
+        ufbx::LightType::Point => Some(FbxLightType::Point),
+        ufbx::LightType::Spot => Some(FbxLightType::Spot),
+        _ => None,
+    }
+}
+
+/// Store a scalar property value in a track value.
+fn scalar(value: f32) -> Vec3 {
+    Vec3::new(value, 0.0, 0.0)
+}
+
+/// Bevy light intensity for a normalized FBX intensity, in lux for
+/// directional lights and lumens otherwise.
+pub fn light_intensity(light_type: FbxLightType, intensity: f32) -> f32 {
+    match light_type {
+        FbxLightType::Directional => intensity * 10000.0,
+        _ => intensity * 1000.0,
+    }
+}
+
+/// Bevy light color for an FBX light color.
+pub fn light_color(color: Vec3) -> Color {
+    Color::srgb(color.x, color.y, color.z)
+}
+
+/// Bevy spot light angle, measured from the light axis in radians, for an FBX
+/// cone angle in degrees.
+pub fn spot_angle(degrees: f32) -> f32 {
+    (degrees / 2.0).to_radians()
+}
+
+/// Vertical field of view in radians from an animated camera property.
+///
+/// Only the property the camera derives its field of view from in its
+/// aperture mode is converted.
+pub fn vertical_fov(camera: &ufbx::Camera, prop_name: &str, value: f64) -> Option<f32> {
+    let aperture = camera.aperture_size_inch;
+    let fov = match (camera.aperture_mode, prop_name) {
+        (ufbx::ApertureMode::FocalLength, "FocalLength") => {
+            focal_length_to_fov(value, aperture.y * MM_PER_INCH)?
+        }
+        (ufbx::ApertureMode::Vertical, "FieldOfView")
+        | (ufbx::ApertureMode::HorizontalAndVertical, "FieldOfViewY") => value.to_radians(),
+        (ufbx::ApertureMode::Horizontal, "FieldOfView") => {
+            let aspect_ratio = if aperture.y > 0.0 {
+                aperture.x / aperture.y
+            } else {
+                camera.aspect_ratio
+            };
+            horizontal_to_vertical_fov(value.to_radians(), aspect_ratio)
+        }
+        _ => return None,
+    };
+    Some(fov as f32)
+}
+
+/// Field of view in radians of a lens with the given focal length, across
+/// an aperture of the given size.
+pub fn focal_length_to_fov(focal_length_mm: f64, aperture_mm: f64) -> Option<f64> {
+    (focal_length_mm > 0.0).then(|| 2.0 * (aperture_mm / (2.0 * focal_length_mm)).atan())
+}
+
+/// Vertical field of view for a horizontal one, both in radians.
+pub fn horizontal_to_vertical_fov(fov: f64, aspect_ratio: f64) -> f64 {
+    2.0 * ((fov / 2.0).tan() / aspect_ratio).atan()
+}
+
+/// Create a curve animating the component property of a track.
+pub fn property_curve(track: &FbxPropertyTrack) -> Option<VariableCurve> {
+    let keyframes = &track.keyframes;
+    match &track.property {
+        FbxAnimatedProperty::Visibility => {
+            float_curve(animated_field!(FbxVisibility::value), keyframes)
+        }
+        FbxAnimatedProperty::UserProperty(name) => {
+            float_curve(UserPropertyField::new(name), keyframes)
+        }
+        FbxAnimatedProperty::FieldOfView => float_curve(PerspectiveFovProperty, keyframes),
+        FbxAnimatedProperty::LightIntensity(FbxLightType::Directional) => {
+            float_curve(animated_field!(DirectionalLight::illuminance), keyframes)
+        }
+        FbxAnimatedProperty::LightIntensity(FbxLightType::Spot) => {
+            float_curve(animated_field!(SpotLight::intensity), keyframes)
+        }
+        FbxAnimatedProperty::LightIntensity(_) => {
+            float_curve(animated_field!(PointLight::intensity), keyframes)
+        }
+        FbxAnimatedProperty::LightColor(FbxLightType::Directional) => {
+            color_curve(LightColorProperty::<DirectionalLight>::default(), keyframes)
+        }
+        FbxAnimatedProperty::LightColor(FbxLightType::Spot) => {
+            color_curve(LightColorProperty::<SpotLight>::default(), keyframes)
+        }
+        FbxAnimatedProperty::LightColor(_) => {
+            color_curve(LightColorProperty::<PointLight>::default(), keyframes)
+        }
+        FbxAnimatedProperty::SpotInnerAngle => {
+            float_curve(animated_field!(SpotLight::inner_angle), keyframes)
+        }
+        FbxAnimatedProperty::SpotOuterAngle => {
+            float_curve(animated_field!(SpotLight::outer_angle), keyframes)
+        }
+    }
+}
+
+/// Create a curve animating an `f32` property from the `x` component of
+/// keyframes.
+pub(crate) fn float_curve(
+    property: impl AnimatableProperty<Property = f32> + Clone,
+    keyframes: &FbxKeyframes<Vec3>,
+) -> Option<VariableCurve> {
+    let samples = keyframes
+        .times
+        .iter()
+        .copied()
+        .zip(keyframes.values.iter().map(|value| value.x));
+
+    match (keyframes.values.len(), keyframes.interpolation) {
+        (0, _) => None,
+        (1, _) => Some(VariableCurve::new(AnimatableCurve::new(
+            property,
+            ConstantCurve::new(Interval::EVERYWHERE, keyframes.values[0].x),
+        ))),
+        (_, FbxInterpolation::Constant) => SteppedKeyframeCurve::new(samples)
+            .ok()
+            .map(|curve| VariableCurve::new(AnimatableCurve::new(property, curve))),
+        _ => UnevenSampleAutoCurve::new(samples)
+            .ok()
+            .map(|curve| VariableCurve::new(AnimatableCurve::new(property, curve))),
+    }
+}
+
+/// Create a curve animating a linear RGB color property from keyframes.
+pub(crate) fn color_curve(
+    property: impl AnimatableProperty<Property = LinearRgba> + Clone,
+    keyframes: &FbxKeyframes<Vec3>,
+) -> Option<VariableCurve> {
+    let samples = keyframes.times.iter().copied().zip(
+        keyframes
+            .values
+            .iter()
+            .map(|&value| LinearRgba::from_vec3(value)),
+    );
+
+    match (keyframes.values.len(), keyframes.interpolation) {
+        (0, _) => None,
+        (1, _) => Some(VariableCurve::new(AnimatableCurve::new(
+            property,
+            ConstantCurve::new(
+                Interval::EVERYWHERE,
+                LinearRgba::from_vec3(keyframes.values[0]),
+            ),
+        ))),
+        (_, FbxInterpolation::Constant) => SteppedKeyframeCurve::new(samples)
+            .ok()
+            .map(|curve| VariableCurve::new(AnimatableCurve::new(property, curve))),
+        _ => AnimatableKeyframeCurve::new(samples)
+            .ok()
+            .map(|curve| VariableCurve::new(AnimatableCurve::new(property, curve))),
+    }
+}
+
+/// Create a curve animating a `Vec2` property from the `x` and `y` components
+/// of keyframes.
+pub(crate) fn vec2_curve(
+    property: impl AnimatableProperty<Property = Vec2> + Clone,
+    keyframes: &FbxKeyframes<Vec3>,
+) -> Option<VariableCurve> {
+    let samples = keyframes
+        .times
+        .iter()
+        .copied()
+        .zip(keyframes.values.iter().map(|value| value.truncate()));
+
+    match (keyframes.values.len(), keyframes.interpolation) {
+        (0, _) => None,
+        (1, _) => Some(VariableCurve::new(AnimatableCurve::new(
+            property,
+            ConstantCurve::new(Interval::EVERYWHERE, keyframes.values[0].truncate()),
+        ))),
+        (_, FbxInterpolation::Constant) => SteppedKeyframeCurve::new(samples)
+            .ok()
+            .map(|curve| VariableCurve::new(AnimatableCurve::new(property, curve))),
+        _ => UnevenSampleAutoCurve::new(samples)
+            .ok()
+            .map(|curve| VariableCurve::new(AnimatableCurve::new(property, curve))),
+    }
+}
+
+/// Animated FBX visibility of a node, applied to its [`Visibility`] by
+/// [`apply_fbx_visibility`].
+#[derive(Component, Reflect, Debug, Clone, Copy, PartialEq)]
+#[reflect(Component)]
+pub struct FbxVisibility {
+    /// Visibility of the node, shown at `0.5` and above
+    pub value: f32,
+}
+
+impl FbxVisibility {
+    pub fn new(visible: bool) -> Self {
+        Self {
+            value: if visible { 1.0 } else { 0.0 },
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.value >= 0.5
+    }
+}
+
+/// Show or hide nodes whose FBX visibility changed.
+pub fn apply_fbx_visibility(
+    mut nodes: Query<(&FbxVisibility, &mut Visibility), Changed<FbxVisibility>>,
+) {
+    for (fbx_visibility, mut visibility) in &mut nodes {
+        visibility.set_if_neq(if fbx_visibility.is_visible() {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        });
+    }
+}
+
+/// Numeric user-defined properties of a node, updated by animation clips that
+/// animate them.
+#[derive(Component, Reflect, Debug, Clone, Default, PartialEq)]
+#[reflect(Component)]
+pub struct FbxUserProperties {
+    pub values: HashMap<String, f32>,
+}
+
+impl FbxUserProperties {
+    /// Current value of a property.
+    pub fn get(&self, name: &str) -> Option<f32> {
+        self.values.get(name).copied()
+    }
+}
+
+/// A property of [`FbxUserProperties`], added to the component when first
+/// animated.
+#[derive(Clone)]
+pub struct UserPropertyField {
+    name: String,
+    /// Component type and hash of the name, so clips animating the same
+    /// property blend together
+    evaluator_id: Hashed<(TypeId, usize)>,
+}
+
+impl UserPropertyField {
+    pub fn new(name: &str) -> Self {
+        let mut hasher = DefaultHasher::new();
+        name.hash(&mut hasher);
+        Self {
+            name: name.to_string(),
+            evaluator_id: Hashed::new((
+                TypeId::of::<FbxUserProperties>(),
+                hasher.finish() as usize,
+            )),
+        }
+    }
+}
+
+impl AnimatableProperty for UserPropertyField {
+    type Property = f32;
+
+    fn get_mut<'a>(
+        &self,
+        entity: &'a mut AnimationEntityMut,
+    ) -> Result<&'a mut f32, AnimationEvaluationError> {
+        let properties = entity
+            .get_mut::<FbxUserProperties>()
+            .ok_or_else(|| {
+                AnimationEvaluationError::ComponentNotPresent(TypeId::of::<FbxUserProperties>())
+            })?
+            .into_inner();
+        Ok(properties.values.entry(self.name.clone()).or_default())
+    }
+
+    fn evaluator_id(&self) -> EvaluatorId<'_> {
+        EvaluatorId::ComponentField(&self.evaluator_id)
+    }
+}
+
+/// Field of view of a perspective [`Projection`].
+#[derive(Clone)]
+pub struct PerspectiveFovProperty;
+
+impl AnimatableProperty for PerspectiveFovProperty {
+    type Property = f32;
+
+    fn get_mut<'a>(
+        &self,
+        entity: &'a mut AnimationEntityMut,
+    ) -> Result<&'a mut f32, AnimationEvaluationError> {
+        let projection = entity
+            .get_mut::<Projection>()
+            .ok_or_else(|| {
+                AnimationEvaluationError::ComponentNotPresent(TypeId::of::<Projection>())
+            })?
+            .into_inner();
+        match projection {
+            Projection::Perspective(perspective) => Ok(&mut perspective.fov),
+            _ => Err(AnimationEvaluationError::PropertyNotPresent(TypeId::of::<
+                PerspectiveProjection,
+            >(
+            ))),
+        }
+    }
+
+    fn evaluator_id(&self) -> EvaluatorId<'_> {
+        EvaluatorId::Type(TypeId::of::<Self>())
+    }
+}
+
+/// Light component with a color.
+pub trait LightColor: Component<Mutability = Mutable> {
+    fn color_mut(&mut self) -> &mut Color;
+}
+
+impl LightColor for DirectionalLight {
+    fn color_mut(&mut self) -> &mut Color {
+        &mut self.color
+    }
+}
+
+impl LightColor for PointLight {
+    fn color_mut(&mut self) -> &mut Color {
+        &mut self.color
+    }
+}
+
+impl LightColor for SpotLight {
+    fn color_mut(&mut self) -> &mut Color {
+        &mut self.color
+    }
+}
+
+/// Color of a light component, animated in linear RGB.
+pub struct LightColorProperty<L>(PhantomData<fn() -> L>);
+
+impl<L> Default for LightColorProperty<L> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<L> Clone for LightColorProperty<L> {
+    fn clone(&self) -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<L: LightColor> AnimatableProperty for LightColorProperty<L> {
+    type Property = LinearRgba;
+
+    fn get_mut<'a>(
+        &self,
+        entity: &'a mut AnimationEntityMut,
+    ) -> Result<&'a mut LinearRgba, AnimationEvaluationError> {
+        let color = entity
+            .get_mut::<L>()
+            .ok_or_else(|| AnimationEvaluationError::ComponentNotPresent(TypeId::of::<L>()))?
+            .into_inner()
+            .color_mut();
+        // Colors are blended in linear RGB, whatever space they were set in
+        *color = Color::LinearRgba(color.to_linear());
+        match color {
+            Color::LinearRgba(linear) => Ok(linear),
+            _ => Err(AnimationEvaluationError::PropertyNotPresent(TypeId::of::<
+                LinearRgba,
+            >(
+            ))),
+        }
+    }
+
+    fn evaluator_id(&self) -> EvaluatorId<'_> {
+        EvaluatorId::Type(TypeId::of::<Self>())
+    }
+}
@@ -0,0 +1,170 @@
+//! Keyframe reduction of converted animations.
+
+use crate::loader::FbxKeyReductionSettings;
+use crate::types::{FbxAnimation, FbxInterpolation, FbxKeyframes};
+use crate::utils::convert_matrix;
+use bevy::animation::animatable::Animatable;
+use bevy::prelude::*;
+
+/// Number of node keys before and after keyframe reduction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FbxKeyReduction {
+    pub keys_before: usize,
+    pub keys_after: usize,
+}
+
+impl FbxKeyReduction {
+    /// Keys before reduction for every key kept, `1.0` when no key was removed.
+    pub fn ratio(&self) -> f32 {
+        if self.keys_after == 0 {
+            1.0
+        } else {
+            self.keys_before as f32 / self.keys_after as f32
+        }
+    }
+}
+
+impl core::ops::AddAssign for FbxKeyReduction {
+    fn add_assign(&mut self, other: Self) {
+        self.keys_before += other.keys_before;
+        self.keys_after += other.keys_after;
+    }
+}
+
+/// Remove the node keys of an animation that the remaining keys reproduce
+/// within the tolerances of `settings`.
+///
+/// `meters_per_unit` holds the size in meters of one unit of translation of
+/// each node, indexed like the scene nodes, as made by
+/// [`translation_units`]. Nodes without an entry use meters.
+pub fn reduce_animation(
+    animation: &mut FbxAnimation,
+    settings: &FbxKeyReductionSettings,
+    meters_per_unit: &[f32],
+) -> FbxKeyReduction {
+    let mut reduction = FbxKeyReduction::default();
+    for track in &mut animation.tracks {
+        reduction.keys_before +=
+            track.translation.times.len() + track.rotation.times.len() + track.scale.times.len();
+
+        let unit = meters_per_unit
+            .get(track.node_index)
+            .copied()
+            .filter(|&unit| unit > 0.0)
+            .unwrap_or(1.0);
+        reduce_keyframes(
+            &mut track.translation,
+            settings.translation / unit,
+            |a, b| a.distance(b),
+        );
+        reduce_keyframes(
+            &mut track.rotation,
+            settings.rotation.to_radians(),
+            rotation_error,
+        );
+        reduce_keyframes(&mut track.scale, settings.scale, |a, b| a.distance(b));
+
+        reduction.keys_after +=
+            track.translation.times.len() + track.rotation.times.len() + track.scale.times.len();
+    }
+    reduction
+}
+
+/// Size in meters of one unit of translation of each node of a scene.
+///
+/// Translation keys are relative to the parent of their node, so they are
+/// scaled by the world scale of the parent. This includes the unit
+/// conversion of the scene root, which leaves nodes below the top level in
+/// the units of the file.
+pub fn translation_units(scene: &ufbx::Scene) -> Vec<f32> {
+    scene
+        .nodes
+        .as_ref()
+        .iter()
+        .map(|node| {
+            node.parent.as_ref().map_or(1.0, |parent| {
+                convert_matrix(&parent.node_to_world)
+                    .to_scale_rotation_translation()
+                    .0
+                    .max_element()
+            })
+        })
+        .collect()
+}
+
+/// Remove keys that the remaining keys reproduce within `tolerance`, as
+/// measured by `error`.
+///
+/// The first and last keys are always kept. Linear keys are reduced with the
+/// Ramer-Douglas-Peucker algorithm, so that no removed key is further than
+/// `tolerance` from the interpolated curve. Stepped keys are removed when they
+/// hold the previous value. Cubic keys and tolerances of `0.0` are left as is.
+pub fn reduce_keyframes<T: Animatable + Copy>(
+    keyframes: &mut FbxKeyframes<T>,
+    tolerance: f32,
+    error: impl Fn(T, T) -> f32,
+) {
+    let len = keyframes.times.len();
+    if tolerance <= 0.0 || len <= 2 || keyframes.values.len() != len {
+        return;
+    }
+
+    let mut keep = vec![false; len];
+    keep[0] = true;
+    keep[len - 1] = true;
+    match keyframes.interpolation {
+        FbxInterpolation::Cubic => return,
+        FbxInterpolation::Constant => {
+            let mut held = 0;
+            for (index, value) in keyframes.values.iter().enumerate().take(len - 1).skip(1) {
+                if error(*value, keyframes.values[held]) > tolerance {
+                    keep[index] = true;
+                    held = index;
+                }
+            }
+        }
+        FbxInterpolation::Linear => {
+            let (times, values) = (&keyframes.times, &keyframes.values);
+            let mut segments = vec![(0, len - 1)];
+            while let Some((start, end)) = segments.pop() {
+                let span = times[end] - times[start];
+                let farthest = (start + 1..end)
+                    .map(|index| {
+                        let t = if span > 0.0 {
+                            (times[index] - times[start]) / span
+                        } else {
+                            0.0
+                        };
+                        let interpolated = T::interpolate(&values[start], &values[end], t);
+                        (index, error(interpolated, values[index]))
+                    })
+                    .max_by(|a, b| a.1.total_cmp(&b.1));
+                if let Some((index, distance)) = farthest
+                    && distance > tolerance
+                {
+                    keep[index] = true;
+                    segments.push((start, index));
+                    segments.push((index, end));
+                }
+            }
+        }
+    }
+
+    retain_kept(&mut keyframes.times, &keep);
+    retain_kept(&mut keyframes.values, &keep);
+    if keyframes.tangents.len() == len {
+        retain_kept(&mut keyframes.tangents, &keep);
+    }
+}
+
+fn retain_kept<T>(items: &mut Vec<T>, keep: &[bool]) {
+    let mut flags = keep.iter();
+    items.retain(|_| *flags.next().unwrap_or(&true));
+}
+
+/// Angle between two rotations, precise for small angles unlike the arc
+/// cosine of their dot product.
+fn rotation_error(a: Quat, b: Quat) -> f32 {
+    let difference = a.inverse() * b;
+    2.0 * difference.xyz().length().atan2(difference.w.abs())
+}
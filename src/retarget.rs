@@ -0,0 +1,437 @@
+//! Binding and retargeting animations to the node hierarchy of another FBX
+//! file.
+
+use crate::animation::{create_animation_clip, make_rotations_continuous};
+use crate::loader::FbxRetargetSettings;
+use crate::node::{animation_target_id, node_name};
+use crate::types::{FbxAnimation, FbxKeyframes, FbxRestBone, Skeleton};
+use crate::utils::convert_matrix;
+use bevy::animation::{AnimationClip, AnimationTargetId};
+use bevy::prelude::*;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// Match the nodes of a scene to the nodes of a target skeleton by name.
+///
+/// Returns the matching skeleton node for each node of `scene`, indexed like
+/// `scene.nodes`. Names that have no exact match are compared again with
+/// their namespace prefixes (`mixamorig:`) removed.
+pub fn match_nodes_by_name<'a>(
+    scene: &ufbx::Scene,
+    skeleton: &'a ufbx::Scene,
+) -> Vec<Option<&'a ufbx::Node>> {
+    let skeleton_nodes: Vec<&ufbx::Node> = skeleton
+        .nodes
+        .as_ref()
+        .iter()
+        .filter(|node| !node.is_root)
+        .map(|node| &**node)
+        .collect();
+    let by_name: HashMap<String, &ufbx::Node> = skeleton_nodes
+        .iter()
+        .map(|&node| (node_name(node), node))
+        .collect();
+    let by_local_name: HashMap<String, &ufbx::Node> = skeleton_nodes
+        .iter()
+        .map(|&node| (strip_namespace(&node_name(node)).to_string(), node))
+        .collect();
+
+    scene
+        .nodes
+        .as_ref()
+        .iter()
+        .map(|node| {
+            if node.is_root {
+                return None;
+            }
+            let name = node_name(node);
+            by_name
+                .get(&name)
+                .or_else(|| by_local_name.get(strip_namespace(&name)))
+                .copied()
+        })
+        .collect()
+}
+
+/// Remove the namespace prefixes of a node name, `mixamorig:Hips` becomes
+/// `Hips`.
+pub fn strip_namespace(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+/// Point the tracks of an animation at the bound animation targets.
+///
+/// Tracks of nodes without a target are dropped and their node indices are
+/// added to `unmatched`. Material tracks target materials by name rather
+/// than nodes, so they are kept unchanged.
+pub fn bind_animation(
+    animation: &mut FbxAnimation,
+    targets: &[Option<AnimationTargetId>],
+    unmatched: &mut BTreeSet<usize>,
+) {
+    let bound = |node_index: usize| targets.get(node_index).copied().flatten();
+    animation
+        .tracks
+        .retain_mut(|track| rebind_track(track.node_index, &mut track.target, bound, unmatched));
+    animation
+        .morph_tracks
+        .retain_mut(|track| rebind_track(track.node_index, &mut track.target, bound, unmatched));
+    animation
+        .property_tracks
+        .retain_mut(|track| rebind_track(track.node_index, &mut track.target, bound, unmatched));
+}
+
+/// Point a track at the target bound to its node.
+///
+/// Returns `false` and adds the node to `unmatched` if it has no target, so
+/// the track can be dropped by `retain_mut`.
+fn rebind_track(
+    node_index: usize,
+    target: &mut AnimationTargetId,
+    bound: impl Fn(usize) -> Option<AnimationTargetId>,
+    unmatched: &mut BTreeSet<usize>,
+) -> bool {
+    match bound(node_index) {
+        Some(bound) => {
+            *target = bound;
+            true
+        }
+        None => {
+            unmatched.insert(node_index);
+            false
+        }
+    }
+}
+
+/// Compute the rest pose of every node of a scene, by node name.
+///
+/// Bones use their skin bind pose where available, other nodes the pose
+/// stored in the file.
+pub fn rest_pose(scene: &ufbx::Scene) -> HashMap<String, FbxRestBone> {
+    let mut world: Vec<Mat4> = scene
+        .nodes
+        .as_ref()
+        .iter()
+        .map(|node| convert_matrix(&node.node_to_world))
+        .collect();
+    for cluster in scene.skin_clusters.as_ref().iter() {
+        if let Some(bone) = cluster.bone_node.as_ref() {
+            world[bone.element.typed_id as usize] = convert_matrix(&cluster.bind_to_world);
+        }
+    }
+
+    scene
+        .nodes
+        .as_ref()
+        .iter()
+        .filter(|node| !node.is_root)
+        .map(|node| {
+            let parent = node.parent.as_ref().filter(|parent| !parent.is_root);
+            let parent_world = parent.map_or(Mat4::IDENTITY, |parent| {
+                world[parent.element.typed_id as usize]
+            });
+            let node_world = world[node.element.typed_id as usize];
+            let bone = FbxRestBone {
+                target: animation_target_id(node),
+                parent: parent.map(|parent| node_name(parent)),
+                local: Transform::from_matrix(parent_world.inverse() * node_world),
+                parent_world,
+                world_translation: node_world.w_axis.truncate(),
+            };
+            (node_name(node), bone)
+        })
+        .collect()
+}
+
+/// Compute the rest pose of the joints of a loaded skeleton, by joint name.
+///
+/// Joints use their bind matrices, like [`rest_pose`] uses the skin bind
+/// pose. The parent of the root joint is placed so that the root keeps its
+/// rest transform.
+pub fn skeleton_rest_pose(skeleton: &Skeleton) -> HashMap<String, FbxRestBone> {
+    (0..skeleton.joint_names.len())
+        .map(|joint| {
+            let node_world = skeleton.bind_matrices[joint];
+            let (parent, parent_world) = match skeleton.parents[joint] {
+                Some(parent) => (
+                    Some(skeleton.joint_names[parent].clone()),
+                    skeleton.bind_matrices[parent],
+                ),
+                None => (
+                    None,
+                    node_world * skeleton.rest_transforms[joint].to_matrix().inverse(),
+                ),
+            };
+            let bone = FbxRestBone {
+                target: skeleton.targets[joint],
+                parent,
+                local: Transform::from_matrix(parent_world.inverse() * node_world),
+                parent_world,
+                world_translation: node_world.w_axis.truncate(),
+            };
+            (skeleton.joint_names[joint].clone(), bone)
+        })
+        .collect()
+}
+
+/// Retarget an animation of a loaded file from one of its skeletons onto the
+/// skeleton of another rig, and create its clip.
+///
+/// `animation` is one of the converted animations in [`Fbx::poses`] of the
+/// file holding `source`. The skeleton of a skin is [`FbxSkin::skeleton`].
+/// Tracks of nodes without a target bone are dropped and their node indices
+/// are added to `unmatched`.
+///
+/// [`Fbx::poses`]: crate::types::Fbx::poses
+/// [`FbxSkin::skeleton`]: crate::types::FbxSkin::skeleton
+pub fn retarget_animation(
+    animation: &FbxAnimation,
+    source: &Skeleton,
+    target: &Skeleton,
+    settings: &FbxRetargetSettings,
+    unmatched: &mut BTreeSet<usize>,
+) -> AnimationClip {
+    let mut animation = animation.clone();
+    Retargeter::from_skeletons(source, target, settings).retarget(&mut animation, unmatched);
+    create_animation_clip(&animation)
+}
+
+/// Retargets animations from a source rig onto a target rig with other bone
+/// names and another rest pose.
+///
+/// Rotations are transferred as world space offsets from the rest pose, so
+/// bones with different local axes perform the same motion. Translation is
+/// only transferred for the root bone, scaled by the ratio of the rest
+/// heights of the two rigs.
+pub struct Retargeter {
+    source: HashMap<String, FbxRestBone>,
+    target: HashMap<String, FbxRestBone>,
+    /// Source and target bone name of each source node
+    pairs: Vec<Option<(String, String)>>,
+    corrections: HashMap<String, Quat>,
+    root_bone: Option<String>,
+}
+
+impl Retargeter {
+    /// Pair the bones of two rest poses.
+    ///
+    /// `source_names` are the names of the source nodes, indexed like the
+    /// `node_index` of the animation tracks.
+    pub fn new(
+        source: HashMap<String, FbxRestBone>,
+        target: HashMap<String, FbxRestBone>,
+        source_names: &[String],
+        settings: &FbxRetargetSettings,
+    ) -> Self {
+        let by_local_name: HashMap<&str, &str> = target
+            .keys()
+            .map(|name| (strip_namespace(name), name.as_str()))
+            .collect();
+
+        let pairs = source_names
+            .iter()
+            .map(|source_name| {
+                let target_name = match settings.bone_map.get(source_name) {
+                    Some(mapped) => target.contains_key(mapped).then_some(mapped.as_str()),
+                    None if target.contains_key(source_name) => Some(source_name.as_str()),
+                    None => by_local_name.get(strip_namespace(source_name)).copied(),
+                };
+                target_name
+                    .filter(|_| source.contains_key(source_name))
+                    .map(|target_name| (source_name.clone(), target_name.to_string()))
+            })
+            .collect();
+
+        let corrections = settings
+            .rest_corrections
+            .iter()
+            .map(|(name, [x, y, z])| {
+                let rotation = Quat::from_euler(
+                    EulerRot::XYZ,
+                    x.to_radians(),
+                    y.to_radians(),
+                    z.to_radians(),
+                );
+                (name.clone(), rotation)
+            })
+            .collect();
+
+        Self {
+            source,
+            target,
+            pairs,
+            corrections,
+            root_bone: settings.root_bone.clone(),
+        }
+    }
+
+    /// Pair the nodes of a scene with the nodes of a target skeleton.
+    pub fn from_scenes(
+        scene: &ufbx::Scene,
+        skeleton: &ufbx::Scene,
+        settings: &FbxRetargetSettings,
+    ) -> Self {
+        let source_names: Vec<String> = scene
+            .nodes
+            .as_ref()
+            .iter()
+            .map(|node| node_name(node))
+            .collect();
+        Self::new(
+            rest_pose(scene),
+            rest_pose(skeleton),
+            &source_names,
+            settings,
+        )
+    }
+
+    /// Pair the joints of two loaded skeletons.
+    ///
+    /// Animations retargeted with it index their tracks by the nodes of the
+    /// file holding `source`.
+    pub fn from_skeletons(
+        source: &Skeleton,
+        target: &Skeleton,
+        settings: &FbxRetargetSettings,
+    ) -> Self {
+        let node_count = source.nodes.iter().max().map_or(0, |node| node + 1);
+        let mut source_names = vec![String::new(); node_count];
+        for (joint, &node) in source.nodes.iter().enumerate() {
+            source_names[node] = source.joint_names[joint].clone();
+        }
+        Self::new(
+            skeleton_rest_pose(source),
+            skeleton_rest_pose(target),
+            &source_names,
+            settings,
+        )
+    }
+
+    /// Rewrite an animation of the source rig for the target rig.
+    ///
+    /// Tracks of bones without a target bone are dropped and their node
+    /// indices are added to `unmatched`. Scale is not transferred. Material
+    /// tracks target materials by name rather than nodes, so they are kept
+    /// unchanged.
+    pub fn retarget(&self, animation: &mut FbxAnimation, unmatched: &mut BTreeSet<usize>) {
+        let root_bones = self.root_bones(animation);
+
+        animation.tracks.retain_mut(|track| {
+            let Some((source_name, target_name)) =
+                self.pairs.get(track.node_index).and_then(Option::as_ref)
+            else {
+                unmatched.insert(track.node_index);
+                return false;
+            };
+            let source = &self.source[source_name];
+            let target = &self.target[target_name];
+
+            let correction = self
+                .corrections
+                .get(target_name)
+                .copied()
+                .unwrap_or(Quat::IDENTITY);
+            let (_, source_parent, _) = source.parent_world.to_scale_rotation_translation();
+            let (_, target_parent, _) = target.parent_world.to_scale_rotation_translation();
+            for rotation in &mut track.rotation.values {
+                let offset = source_parent
+                    * *rotation
+                    * source.local.rotation.inverse()
+                    * source_parent.inverse();
+                *rotation = target_parent.inverse()
+                    * offset
+                    * target_parent
+                    * target.local.rotation
+                    * correction;
+            }
+            make_rotations_continuous(&mut track.rotation.values);
+
+            if root_bones.contains(source_name.as_str()) {
+                let scale = if source.world_translation.y.abs() > f32::EPSILON {
+                    target.world_translation.y / source.world_translation.y
+                } else {
+                    1.0
+                };
+                let to_target = target.parent_world.inverse();
+                let transfer = |offset: Vec3| {
+                    to_target
+                        .transform_vector3(source.parent_world.transform_vector3(offset) * scale)
+                };
+                for translation in &mut track.translation.values {
+                    *translation = target.local.translation
+                        + transfer(*translation - source.local.translation);
+                }
+                for (tangent_in, tangent_out) in &mut track.translation.tangents {
+                    *tangent_in = transfer(*tangent_in);
+                    *tangent_out = transfer(*tangent_out);
+                }
+            } else {
+                // Other bones keep the proportions of the target rig
+                track.translation = FbxKeyframes::default();
+            }
+            track.scale = FbxKeyframes::default();
+            track.target = target.target;
+            true
+        });
+
+        // Morph targets, cameras and lights are only kept where the target has
+        // a node of that name
+        let paired = |node_index: usize| self.paired_target(node_index);
+        animation.morph_tracks.retain_mut(|track| {
+            rebind_track(track.node_index, &mut track.target, paired, unmatched)
+        });
+        animation.property_tracks.retain_mut(|track| {
+            rebind_track(track.node_index, &mut track.target, paired, unmatched)
+        });
+        // Material tracks are left as they are, animating the materials of the
+        // same name in the target
+    }
+
+    /// Animation target of the target bone paired with a source node.
+    fn paired_target(&self, node_index: usize) -> Option<AnimationTargetId> {
+        self.pairs
+            .get(node_index)
+            .and_then(Option::as_ref)
+            .and_then(|(_, target_name)| self.target.get(target_name))
+            .map(|target| target.target)
+    }
+
+    /// Source bones whose translation is transferred: the configured root
+    /// bone, or the topmost paired bones with animated translation.
+    fn root_bones(&self, animation: &FbxAnimation) -> HashSet<&str> {
+        if let Some(root_bone) = &self.root_bone {
+            return HashSet::from([root_bone.as_str()]);
+        }
+
+        let translated: Vec<&str> = animation
+            .tracks
+            .iter()
+            .filter(|track| {
+                track
+                    .translation
+                    .values
+                    .windows(2)
+                    .any(|pair| pair[0] != pair[1])
+            })
+            .filter_map(|track| self.pairs.get(track.node_index)?.as_ref())
+            .map(|(source_name, _)| source_name.as_str())
+            .collect();
+        let Some(min_depth) = translated.iter().map(|name| self.depth(name)).min() else {
+            return HashSet::new();
+        };
+        translated
+            .into_iter()
+            .filter(|name| self.depth(name) == min_depth)
+            .collect()
+    }
+
+    /// Number of ancestors of a source bone.
+    fn depth(&self, name: &str) -> usize {
+        let mut depth = 0;
+        let mut current = self.source.get(name);
+        while let Some(parent) = current.and_then(|bone| bone.parent.as_ref()) {
+            depth += 1;
+            current = self.source.get(parent);
+        }
+        depth
+    }
+}
@@ -0,0 +1,114 @@
+//! Root motion extraction for FBX animations.
+
+use crate::animation::{make_rotations_continuous, sample_keyframes};
+use crate::types::{FbxAnimation, FbxKeyframes, FbxRootMotion};
+use bevy::prelude::*;
+
+/// Move the horizontal translation and yaw of a joint out of an animation.
+///
+/// The joint keeps its height and the tilt of its rotation, so the animation
+/// plays in place. The tracks are local to the joint's parent, so
+/// `parent_to_world` moves them into the Y-up space of the scene before they
+/// are split, and the root motion is returned in that space. Returns `None`
+/// if the joint is not animated.
+pub fn extract_root_motion(
+    animation: &mut FbxAnimation,
+    node_index: usize,
+    parent_to_world: Mat4,
+) -> Option<FbxRootMotion> {
+    let to_scene = Mat3::from_mat4(parent_to_world);
+    let to_local = to_scene.inverse();
+    let (_, parent_rotation, _) = parent_to_world.to_scale_rotation_translation();
+
+    let track = animation
+        .tracks
+        .iter_mut()
+        .find(|track| track.node_index == node_index)?;
+
+    // Horizontal offset from the first frame
+    let mut translation = FbxKeyframes {
+        interpolation: track.translation.interpolation,
+        times: track.translation.times.clone(),
+        ..Default::default()
+    };
+    if let Some(&origin) = track.translation.values.first() {
+        for value in &mut track.translation.values {
+            let offset = horizontal(to_scene * (*value - origin));
+            translation.values.push(offset);
+            *value -= to_local * offset;
+        }
+        for (tangent_in, tangent_out) in &mut track.translation.tangents {
+            let offset_in = horizontal(to_scene * *tangent_in);
+            let offset_out = horizontal(to_scene * *tangent_out);
+            translation.tangents.push((offset_in, offset_out));
+            *tangent_in -= to_local * offset_in;
+            *tangent_out -= to_local * offset_out;
+        }
+    }
+
+    // Yaw relative to the first frame
+    let mut rotation = FbxKeyframes {
+        interpolation: track.rotation.interpolation,
+        times: track.rotation.times.clone(),
+        ..Default::default()
+    };
+    if let Some(&origin) = track.rotation.values.first() {
+        let origin_yaw = yaw(parent_rotation * origin);
+        for value in &mut track.rotation.values {
+            let offset = yaw(parent_rotation * *value) * origin_yaw.inverse();
+            rotation.values.push(offset);
+            *value = parent_rotation.inverse() * offset.inverse() * parent_rotation * *value;
+        }
+        make_rotations_continuous(&mut rotation.values);
+        make_rotations_continuous(&mut track.rotation.values);
+    }
+
+    Some(FbxRootMotion {
+        node_index,
+        duration: animation.duration,
+        translation,
+        rotation,
+    })
+}
+
+/// The horizontal part of a vector in the Y-up space of the scene.
+fn horizontal(value: Vec3) -> Vec3 {
+    Vec3::new(value.x, 0.0, value.z)
+}
+
+/// The rotation around the Y axis contained in a rotation.
+pub fn yaw(rotation: Quat) -> Quat {
+    let twist = Quat::from_xyzw(0.0, rotation.y, 0.0, rotation.w);
+    if twist.length_squared() > f32::EPSILON {
+        twist.normalize()
+    } else {
+        Quat::IDENTITY
+    }
+}
+
+impl FbxRootMotion {
+    /// Offset of the root from the start of the animation at `time`.
+    pub fn sample(&self, time: f32) -> Transform {
+        Transform {
+            translation: sample_keyframes(&self.translation, time).unwrap_or(Vec3::ZERO),
+            rotation: sample_keyframes(&self.rotation, time).unwrap_or(Quat::IDENTITY),
+            scale: Vec3::ONE,
+        }
+    }
+
+    /// Motion of the root between two times, relative to its pose at `from`.
+    ///
+    /// Apply it to the character each frame with the previous and current
+    /// playback time. Wrapping around a looping clip should be split into two
+    /// calls at the end and start of the clip.
+    pub fn delta(&self, from: f32, to: f32) -> Transform {
+        let start = self.sample(from);
+        let end = self.sample(to);
+        let inverse = start.rotation.inverse();
+        Transform {
+            translation: inverse * (end.translation - start.translation),
+            rotation: inverse * end.rotation,
+            scale: Vec3::ONE,
+        }
+    }
+}
@@ -1,26 +1,62 @@
 //! Scene building functionality for FBX files.
 
+use crate::constraint::node_constraints;
 use crate::error::FbxError;
+use crate::geometry_cache::{FbxVertexAnimation, FbxVertexAnimationPlayer};
 use crate::label::FbxAssetLabel;
 use crate::loader::FbxLoaderSettings;
-use crate::utils::convert_matrix;
+use crate::material::{animated_material_properties, material_target_id, FbxMaterialAnimation};
+use crate::mesh::{morph_target_weights, skin_clusters, FbxJointPalette};
+use crate::node::{animation_target_id, node_name};
+use crate::property::{light_color, light_intensity, spot_angle, user_properties, FbxVisibility};
+use crate::types::FbxLightType;
+use crate::utils::{convert_matrix, convert_transform, convert_vec3};
+use bevy::animation::graph::{AnimationGraph, AnimationGraphHandle, AnimationNodeIndex};
+use bevy::animation::{AnimatedBy, AnimationPlayer};
 use bevy::asset::{Handle, LoadContext};
+use bevy::camera::ScalingMode;
+use bevy::mesh::morph::{MeshMorphWeights, MorphWeights};
+use bevy::mesh::skinning::SkinnedMesh;
 use bevy::pbr::StandardMaterial;
 use bevy::prelude::*;
 use bevy::world_serialization::WorldAsset;
 use std::collections::HashMap;
 
+/// Assets and lookup tables of a file that its scene is built from.
+pub struct SceneContext<'a> {
+    pub meshes: &'a [Handle<Mesh>],
+    pub materials: &'a [Handle<StandardMaterial>],
+    pub named_materials: &'a HashMap<Box<str>, Handle<StandardMaterial>>,
+    /// Node index of each mesh in `meshes`
+    pub mesh_nodes: &'a [usize],
+    /// Material names of each mesh in `meshes`
+    pub mesh_material_info: &'a [Vec<String>],
+    /// Vertex animation of each node with a geometry cache, by node index
+    pub vertex_animations: &'a HashMap<usize, Handle<FbxVertexAnimation>>,
+    /// Joints of each skinned mesh in `meshes`
+    pub joint_palettes: &'a [Option<FbxJointPalette>],
+    pub animation_graph: Option<&'a Handle<AnimationGraph>>,
+    pub animation_graph_nodes: &'a [AnimationNodeIndex],
+}
+
 /// Build the final scene with all entities.
 pub fn build_scene(
     scene: &ufbx::Scene,
-    meshes: &[Handle<Mesh>],
-    materials: &[Handle<StandardMaterial>],
-    named_materials: &HashMap<Box<str>, Handle<StandardMaterial>>,
-    mesh_transforms: &[ufbx::Matrix],
-    mesh_material_info: &[Vec<String>],
+    context: &SceneContext,
     settings: &FbxLoaderSettings,
     load_context: &mut LoadContext,
 ) -> Result<Handle<WorldAsset>, FbxError> {
+    let SceneContext {
+        meshes,
+        materials,
+        named_materials,
+        mesh_nodes,
+        mesh_material_info,
+        vertex_animations,
+        joint_palettes,
+        animation_graph,
+        animation_graph_nodes,
+    } = *context;
     let mut world = World::new();
 
     // Create default material if needed
@@ -31,14 +67,43 @@ pub fn build_scene(
         )
     });
 
+    // Spawn node hierarchy
+    let mut node_entities = vec![Entity::PLACEHOLDER; scene.nodes.len()];
+    let root = spawn_node(&mut world, &scene.root_node, None, &mut node_entities);
+
+    // Animation targets are keyed by name path, so every node is wired up
+    // and clips from other files can drive this hierarchy as well.
+    for node in scene.nodes.as_ref().iter() {
+        if node.is_root {
+            continue;
+        }
+        world
+            .entity_mut(node_entities[node.element.typed_id as usize])
+            .insert((animation_target_id(node), AnimatedBy(root)));
+    }
+
+    let mut player = AnimationPlayer::default();
+    if let Some(node_index) = settings
+        .autoplay_animation
+        .and_then(|index| animation_graph_nodes.get(index))
+    {
+        player.play(*node_index).repeat();
+    }
+    let mut root_entity = world.entity_mut(root);
+    root_entity.insert(player);
+    if let Some(graph) = animation_graph {
+        root_entity.insert(AnimationGraphHandle(graph.clone()));
+    }
+
     // Spawn meshes
-    for (mesh_index, ((mesh_handle, transform_matrix), mat_names)) in meshes
+    for (mesh_index, ((mesh_handle, &node_index), mat_names)) in meshes
         .iter()
-        .zip(mesh_transforms.iter())
+        .zip(mesh_nodes.iter())
         .zip(mesh_material_info.iter())
         .enumerate()
     {
-        let transform = Transform::from_matrix(convert_matrix(transform_matrix));
+        let node = &scene.nodes[node_index];
+        let transform = Transform::from_matrix(convert_matrix(&node.geometry_to_node));
 
         // Find material
         let material = mat_names
@@ -52,18 +117,86 @@ pub fn build_scene(
             })
             .unwrap_or_else(|| default_material.clone());
 
-        world.spawn((
+        let mut mesh_entity = world.spawn((
             Mesh3d(mesh_handle.clone()),
             MeshMaterial3d(material),
             transform,
             GlobalTransform::default(),
             Visibility::default(),
+            ChildOf(node_entities[node_index]),
         ));
+
+        // Animated materials are driven through a component on each entity
+        // using them, targeted by the material name
+        if let Some(ufbx_material) = scene.materials.as_ref().iter().find(|material| {
+            mat_names
+                .iter()
+                .any(|name| material.element.name.as_ref() == name.as_str())
+        }) {
+            let animated = animated_material_properties(scene, ufbx_material);
+            if !animated.is_empty() {
+                mesh_entity.insert((
+                    FbxMaterialAnimation {
+                        animated,
+                        ..FbxMaterialAnimation::from_material(ufbx_material)
+                    },
+                    material_target_id(ufbx_material),
+                    AnimatedBy(root),
+                ));
+            }
+        }
+
+        if let Some(animation) = vertex_animations.get(&node_index) {
+            mesh_entity.insert(FbxVertexAnimationPlayer::new(animation.clone()));
+        }
+
+        // Joints are the entities of the bone nodes, in palette order so that
+        // they line up with the joint indices of the mesh
+        if let Some(Some(joint_palette)) = joint_palettes.get(mesh_index)
+            && let Some(mesh) = node.mesh.as_ref()
+        {
+            mesh_entity.insert(SkinnedMesh {
+                inverse_bindposes: joint_palette.inverse_bindposes.clone(),
+                joints: skin_joints(
+                    &skin_clusters(mesh),
+                    &joint_palette.joints,
+                    node_entities[node_index],
+                    &node_entities,
+                ),
+            });
+        }
+
+        // Morph weights are animated on the node entity, which every mesh
+        // entity spawned for it references
+        let weights = node
+            .mesh
+            .as_ref()
+            .map(|mesh| morph_target_weights(mesh, |channel| channel.weight as f32))
+            .unwrap_or_default();
+        if weights.is_empty() {
+            continue;
+        }
+        mesh_entity.insert(MeshMorphWeights::Reference(node_entities[node_index]));
+        let mut node_entity = world.entity_mut(node_entities[node_index]);
+        if !node_entity.contains::<MorphWeights>() {
+            node_entity.insert(
+                MorphWeights::new(weights, Some(mesh_handle.clone()))
+                    .map_err(|e| FbxError::MeshConversion(e.to_string()))?,
+            );
+        }
     }
 
-    // Spawn lights
+    // Spawn cameras and lights
+    if settings.load_cameras {
+        spawn_cameras(scene, &mut world, &node_entities);
+    }
     if settings.load_lights {
-        spawn_lights(scene, &mut world);
+        spawn_lights(scene, &mut world, &node_entities);
+    }
+
+    // Baked constraints are already part of the animation clips
+    if !settings.animation.bake_constraints {
+        spawn_constraints(scene, &mut world, &node_entities);
     }
 
     let scene_handle =
@@ -72,71 +205,160 @@ pub fn build_scene(
     Ok(scene_handle)
 }
 
-/// Spawn lights in the scene.
-pub fn spawn_lights(scene: &ufbx::Scene, world: &mut World) {
-    for light in scene.lights.as_ref().iter() {
-        if let Some(light_node) = scene.nodes.as_ref().iter().find(|n| {
-            n.light.is_some()
-                && n.light.as_ref().unwrap().element.element_id == light.element.element_id
-        }) {
-            let transform = Transform::from_matrix(convert_matrix(&light_node.node_to_world));
-
-            match light.type_ {
-                ufbx::LightType::Directional => {
-                    world.spawn((
-                        DirectionalLight {
-                            color: Color::srgb(
-                                light.color.x as f32,
-                                light.color.y as f32,
-                                light.color.z as f32,
-                            ),
-                            illuminance: light.intensity as f32 * 10000.0,
-                            shadow_maps_enabled: light.cast_shadows,
-                            ..Default::default()
-                        },
-                        transform,
-                        GlobalTransform::default(),
-                        Visibility::default(),
-                    ));
-                }
-                ufbx::LightType::Point => {
-                    world.spawn((
-                        PointLight {
-                            color: Color::srgb(
-                                light.color.x as f32,
-                                light.color.y as f32,
-                                light.color.z as f32,
-                            ),
-                            intensity: light.intensity as f32 * 1000.0,
-                            shadow_maps_enabled: light.cast_shadows,
-                            ..Default::default()
-                        },
-                        transform,
-                        GlobalTransform::default(),
-                        Visibility::default(),
-                    ));
-                }
-                ufbx::LightType::Spot => {
-                    world.spawn((
-                        SpotLight {
-                            color: Color::srgb(
-                                light.color.x as f32,
-                                light.color.y as f32,
-                                light.color.z as f32,
-                            ),
-                            intensity: light.intensity as f32 * 1000.0,
-                            shadow_maps_enabled: light.cast_shadows,
-                            inner_angle: light.inner_angle as f32,
-                            outer_angle: light.outer_angle as f32,
-                            ..Default::default()
-                        },
-                        transform,
-                        GlobalTransform::default(),
-                        Visibility::default(),
-                    ));
-                }
-                _ => {}
+/// Entities of the bones of the clusters in a joint palette.
+///
+/// Clusters without a bone node are bound to `fallback`, so that joint
+/// indices stay aligned with the palette.
+pub fn skin_joints(
+    clusters: &[&ufbx::SkinCluster],
+    palette: &[u16],
+    fallback: Entity,
+    node_entities: &[Entity],
+) -> Vec<Entity> {
+    palette
+        .iter()
+        .map(|&joint| {
+            clusters
+                .get(joint as usize)
+                .and_then(|cluster| cluster.bone_node.as_ref())
+                .map_or(fallback, |bone| {
+                    node_entities[bone.element.typed_id as usize]
+                })
+        })
+        .collect()
+}
+
+/// Spawn an entity for a node and, recursively, for its children.
+fn spawn_node(
+    world: &mut World,
+    node: &ufbx::Node,
+    parent: Option<Entity>,
+    node_entities: &mut [Entity],
+) -> Entity {
+    let visibility = if node.visible {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+
+    let mut entity = world.spawn((
+        Name::new(node_name(node)),
+        convert_transform(&node.local_transform),
+        GlobalTransform::default(),
+        visibility,
+        FbxVisibility::new(node.visible),
+    ));
+    if let Some(parent) = parent {
+        entity.insert(ChildOf(parent));
+    }
+    let properties = user_properties(node);
+    if !properties.values.is_empty() {
+        entity.insert(properties);
+    }
+    let entity = entity.id();
+    node_entities[node.element.typed_id as usize] = entity;
+
+    for child in node.children.as_ref().iter() {
+        spawn_node(world, child, Some(entity), node_entities);
+    }
+
+    entity
+}
+
+/// Add camera components to the entities of nodes carrying cameras.
+///
+/// Cameras are spawned inactive, so they do not compete with the cameras of
+/// the app until one is activated.
+pub fn spawn_cameras(scene: &ufbx::Scene, world: &mut World, node_entities: &[Entity]) {
+    for camera_node in scene.nodes.as_ref().iter() {
+        let Some(camera) = camera_node.camera.as_ref() else {
+            continue;
+        };
+
+        let projection = match camera.projection_mode {
+            ufbx::ProjectionMode::Perspective => Projection::Perspective(PerspectiveProjection {
+                fov: (camera.field_of_view_deg.y as f32).to_radians(),
+                aspect_ratio: camera.aspect_ratio as f32,
+                near: camera.near_plane as f32,
+                far: camera.far_plane as f32,
+                ..Default::default()
+            }),
+            ufbx::ProjectionMode::Orthographic => {
+                Projection::Orthographic(OrthographicProjection {
+                    near: camera.near_plane as f32,
+                    far: camera.far_plane as f32,
+                    scaling_mode: ScalingMode::FixedVertical {
+                        viewport_height: camera.orthographic_size.y as f32,
+                    },
+                    ..OrthographicProjection::default_3d()
+                })
+            }
+        };
+
+        world
+            .entity_mut(node_entities[camera_node.element.typed_id as usize])
+            .insert((
+                Camera3d::default(),
+                Camera {
+                    is_active: false,
+                    ..Default::default()
+                },
+                projection,
+            ));
+    }
+}
+
+/// Add constraint components to the entities of constrained nodes.
+///
+/// Only the first constraint of each node is kept.
+pub fn spawn_constraints(scene: &ufbx::Scene, world: &mut World, node_entities: &[Entity]) {
+    let constraints = node_constraints(scene, |node| node_entities[node.element.typed_id as usize]);
+    for (_, node, converted) in constraints {
+        world
+            .entity_mut(node_entities[node.element.typed_id as usize])
+            .insert(converted);
+    }
+}
+
+/// Add light components to the entities of nodes carrying lights.
+pub fn spawn_lights(scene: &ufbx::Scene, world: &mut World, node_entities: &[Entity]) {
+    for light_node in scene.nodes.as_ref().iter() {
+        let Some(light) = light_node.light.as_ref() else {
+            continue;
+        };
+        let mut entity = world.entity_mut(node_entities[light_node.element.typed_id as usize]);
+
+        let color = light_color(convert_vec3(&light.color));
+        let intensity = light.intensity as f32;
+
+        match light.type_ {
+            ufbx::LightType::Directional => {
+                entity.insert(DirectionalLight {
+                    color,
+                    illuminance: light_intensity(FbxLightType::Directional, intensity),
+                    shadow_maps_enabled: light.cast_shadows,
+                    ..Default::default()
+                });
+            }
+            ufbx::LightType::Point => {
+                entity.insert(PointLight {
+                    color,
+                    intensity: light_intensity(FbxLightType::Point, intensity),
+                    shadow_maps_enabled: light.cast_shadows,
+                    ..Default::default()
+                });
+            }
+            ufbx::LightType::Spot => {
+                entity.insert(SpotLight {
+                    color,
+                    intensity: light_intensity(FbxLightType::Spot, intensity),
+                    shadow_maps_enabled: light.cast_shadows,
+                    inner_angle: spot_angle(light.inner_angle as f32),
+                    outer_angle: spot_angle(light.outer_angle as f32),
+                    ..Default::default()
+                });
             }
+            _ => {}
         }
     }
 }
@@ -1,10 +1,15 @@
 //! Type definitions for the FBX loader.
 
+use crate::geometry_cache::FbxVertexAnimation;
+use crate::pose::FbxPoseData;
+use crate::reduction::FbxKeyReduction;
+use bevy::animation::graph::{AnimationGraph, AnimationNodeIndex};
+use bevy::animation::{AnimationClip, AnimationEvent, AnimationTargetId};
 use bevy::asset::{Asset, Handle};
 use bevy::math::Affine2;
+use bevy::mesh::skinning::SkinnedMeshInverseBindposes;
 use bevy::pbr::StandardMaterial;
 use bevy::prelude::*;
-use bevy::mesh::skinning::SkinnedMeshInverseBindposes;
 use bevy::world_serialization::WorldAsset;
 use std::collections::HashMap;
 
@@ -20,13 +25,69 @@ pub enum Handedness {
 }
 
 /// Coordinate axes definition.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct FbxAxisSystem {
     pub up: Vec3,
     pub front: Vec3,
     pub handedness: Handedness,
 }
 
+// ============================================================================
+// Scene Settings
+// ============================================================================
+
+/// Time mode of an FBX timeline, which sets its frame rate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FbxTimeMode {
+    Default,
+    Fps120,
+    Fps100,
+    Fps60,
+    Fps50,
+    Fps48,
+    Fps30,
+    Fps30Drop,
+    NtscDropFrame,
+    NtscFullFrame,
+    Pal,
+    Fps24,
+    Fps1000,
+    FilmFullFrame,
+    /// Frame rate set by `CustomFrameRate`
+    Custom,
+    Fps96,
+    Fps72,
+    Fps59_94,
+}
+
+/// Scene-wide settings of an FBX file, as authored.
+///
+/// Axes and units are those of the file, before the scene is converted to
+/// Bevy's Y-up axes and meters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FbxSceneSettings {
+    pub time_mode: FbxTimeMode,
+    /// Frames per second of the timeline
+    pub frames_per_second: f64,
+    /// Start of the timeline in seconds
+    pub time_start: f64,
+    /// End of the timeline in seconds
+    pub time_stop: f64,
+    /// Axes of the file
+    pub axes: FbxAxisSystem,
+    /// Up axis of the file before conversion to Y up, if recorded
+    pub original_up: Option<Vec3>,
+    /// Front axis of the file before conversion to Y up, if recorded
+    pub original_front: Option<Vec3>,
+    /// Handedness of the axes of the file
+    pub handedness: Handedness,
+    /// Length of a file unit in meters
+    pub unit_meters: f32,
+    /// Length of a unit in meters in the application the file was exported from
+    pub original_unit_meters: f32,
+    pub ambient_color: Color,
+}
+
 // ============================================================================
 // Metadata
 // ============================================================================
@@ -157,20 +218,363 @@ pub struct FbxSkin {
     pub name: String,
     pub joints: Vec<Handle<FbxNode>>,
     pub inverse_bind_matrices: Handle<SkinnedMeshInverseBindposes>,
+    /// Skeleton holding the joints
+    pub skeleton: Option<Handle<Skeleton>>,
+    /// Humanoid bones recognised among the joints
+    pub humanoid: Option<Handle<FbxHumanoid>>,
+}
+
+/// Skinning method of an FBX skin deformer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FbxSkinningMethod {
+    Linear,
+    Rigid,
+    DualQuaternion,
+    /// Blend between linear and dual quaternion skinning by a per-vertex weight
+    BlendedDqLinear,
+}
+
+/// Skinning of a mesh which its converted meshes do not reproduce exactly.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FbxSkinningWarning {
+    /// Vertices lost influences to
+    /// [`FbxSkinningSettings::max_influences`](crate::FbxSkinningSettings::max_influences)
+    InfluencesDropped {
+        mesh: String,
+        vertices: usize,
+        /// Largest weight dropped, before renormalization
+        max_dropped_weight: f32,
+    },
+    /// Several skin deformers influence the same vertices, whose weights were
+    /// combined into one blend
+    OverlappingDeformers {
+        mesh: String,
+        deformers: usize,
+        vertices: usize,
+    },
+    /// Skinning method which Bevy does not support, replaced by linear blend
+    /// skinning
+    UnsupportedMethod {
+        mesh: String,
+        method: FbxSkinningMethod,
+    },
+    /// Vertices of a skinned mesh without any weight, bound rigidly to a
+    /// single joint
+    UnweightedVertices { mesh: String, vertices: usize },
+    /// Skin clusters without a bone node, whose vertices follow the skinned
+    /// mesh node instead
+    UnboundClusters { mesh: String, clusters: usize },
+}
+
+impl core::fmt::Display for FbxSkinningWarning {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            FbxSkinningWarning::InfluencesDropped {
+                mesh,
+                vertices,
+                max_dropped_weight,
+            } => write!(
+                f,
+                "Mesh {mesh} has {vertices} vertices with more influences than the limit, dropped weights up to {max_dropped_weight:.3}"
+            ),
+            FbxSkinningWarning::OverlappingDeformers {
+                mesh,
+                deformers,
+                vertices,
+            } => write!(
+                f,
+                "Mesh {mesh} has {vertices} vertices influenced by several of its {deformers} skin deformers, their weights were combined"
+            ),
+            FbxSkinningWarning::UnsupportedMethod { mesh, method } => write!(
+                f,
+                "Mesh {mesh} uses {method:?} skinning, which is replaced by linear blend skinning"
+            ),
+            FbxSkinningWarning::UnweightedVertices { mesh, vertices } => write!(
+                f,
+                "Mesh {mesh} has {vertices} skinned vertices without weights, bound to a single joint"
+            ),
+            FbxSkinningWarning::UnboundClusters { mesh, clusters } => write!(
+                f,
+                "Mesh {mesh} has {clusters} skin clusters without a bone, bound to the mesh node"
+            ),
+        }
+    }
+}
+
+/// Side of a paired humanoid bone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HumanoidSide {
+    Left,
+    Right,
+}
+
+/// Finger of a humanoid hand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HumanoidFinger {
+    Thumb,
+    Index,
+    Middle,
+    Ring,
+    Little,
+}
+
+/// Bone of a humanoid skeleton.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HumanoidBone {
+    Hips,
+    Spine,
+    Chest,
+    UpperChest,
+    Neck,
+    Head,
+    Shoulder(HumanoidSide),
+    UpperArm(HumanoidSide),
+    LowerArm(HumanoidSide),
+    Hand(HumanoidSide),
+    UpperLeg(HumanoidSide),
+    LowerLeg(HumanoidSide),
+    Foot(HumanoidSide),
+    Toes(HumanoidSide),
+    /// Finger segment, numbered 1 to 3 from the hand outwards
+    Finger(HumanoidSide, HumanoidFinger, u8),
+}
+
+/// Naming scheme a humanoid skeleton was recognised by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HumanoidNaming {
+    /// Mixamo `mixamorig:` bones
+    Mixamo,
+    /// 3ds Max Biped `Bip01` bones
+    Biped,
+    /// MotionBuilder HumanIK character bones
+    HumanIk,
 }
 
-/// Placeholder for skeleton data.
+/// Joint mapped to a humanoid bone.
+#[derive(Debug, Clone)]
+pub struct FbxHumanoidJoint {
+    pub name: String,
+    pub node: Handle<FbxNode>,
+    pub target: AnimationTargetId,
+}
+
+/// Humanoid bones of a skin, recognised from common naming schemes.
 #[derive(Asset, Debug, Clone, TypePath)]
-pub struct Skeleton;
+pub struct FbxHumanoid {
+    pub naming: HumanoidNaming,
+    pub bones: HashMap<HumanoidBone, FbxHumanoidJoint>,
+    /// Whether every bone needed to drive a full body was found
+    pub complete: bool,
+}
 
-/// Animation interpolation modes.
+/// Kind of node a skeleton joint was authored as.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FbxJointKind {
+    /// Skeleton bone
+    LimbNode,
+    /// Null node, such as a group between bones or a null bound by a skin
+    Null,
+    /// Node with another attribute, such as a mesh, bound by a skin
+    Other,
+}
+
+/// Joint hierarchy of a chain of bones under one root bone.
+///
+/// Joints are listed parents first. Joint `i` of every field belongs to the
+/// same node.
+#[derive(Asset, Debug, Clone, TypePath)]
+pub struct Skeleton {
+    pub index: usize,
+    /// Name of the root joint
+    pub name: String,
+    pub joint_names: Vec<String>,
+    /// Parent of each joint within the skeleton, `None` for the root
+    pub parents: Vec<Option<usize>>,
+    /// Rest transform of each joint relative to its parent
+    pub rest_transforms: Vec<Transform>,
+    /// Transform of each joint relative to the world at bind time, taken from
+    /// the skin clusters bound to it, or from the rest pose for joints without
+    pub bind_matrices: Vec<Mat4>,
+    pub kinds: Vec<FbxJointKind>,
+    /// Index of the node of each joint in [`Fbx::nodes`]
+    pub nodes: Vec<usize>,
+    /// Animation target of each joint, as used by the clips of the file
+    pub targets: Vec<AnimationTargetId>,
+    /// Humanoid bones recognised among the joints
+    pub humanoid: Option<Handle<FbxHumanoid>>,
+}
+
+impl Skeleton {
+    /// Index of the joint named `name`.
+    pub fn joint_index(&self, name: &str) -> Option<usize> {
+        self.joint_names.iter().position(|joint| joint == name)
+    }
+}
+
+/// Animation interpolation modes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum FbxInterpolation {
     Constant,
+    #[default]
     Linear,
     Cubic,
 }
 
+// ============================================================================
+// Animation
+// ============================================================================
+
+/// Keyframes of a single animated channel, with times in seconds.
+#[derive(Debug, Clone, Default)]
+pub struct FbxKeyframes<T> {
+    pub interpolation: FbxInterpolation,
+    pub times: Vec<f32>,
+    pub values: Vec<T>,
+    /// In and out tangents of each key, only used by cubic interpolation
+    pub tangents: Vec<(T, T)>,
+}
+
+/// Animated transform channels of a single node.
+#[derive(Debug, Clone)]
+pub struct FbxNodeTrack {
+    pub node_index: usize,
+    pub target: AnimationTargetId,
+    pub translation: FbxKeyframes<Vec3>,
+    pub rotation: FbxKeyframes<Quat>,
+    pub scale: FbxKeyframes<Vec3>,
+}
+
+/// Animated morph target weights of a single node.
+#[derive(Debug, Clone)]
+pub struct FbxMorphTrack {
+    pub node_index: usize,
+    pub target: AnimationTargetId,
+    pub times: Vec<f32>,
+    /// Weights of every morph target, stored contiguously for each time
+    pub weights: Vec<f32>,
+}
+
+/// Non-transform property of a node animated by an FBX curve.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FbxAnimatedProperty {
+    /// Visibility of the node, visible from `0.5`
+    Visibility,
+    /// Numeric user-defined property of the node, by name
+    UserProperty(String),
+    /// Vertical field of view of a perspective camera, in radians
+    FieldOfView,
+    /// Intensity of a point or spot light, or illuminance of a directional light
+    LightIntensity(FbxLightType),
+    /// Color of a light, in linear RGB
+    LightColor(FbxLightType),
+    /// Inner cone angle of a spot light, in radians
+    SpotInnerAngle,
+    /// Outer cone angle of a spot light, in radians
+    SpotOuterAngle,
+}
+
+/// Animated non-transform property of a single node.
+#[derive(Debug, Clone)]
+pub struct FbxPropertyTrack {
+    pub node_index: usize,
+    pub target: AnimationTargetId,
+    pub property: FbxAnimatedProperty,
+    /// Keys in Bevy units, scalar properties use the `x` component
+    pub keyframes: FbxKeyframes<Vec3>,
+}
+
+/// Material property animated by an FBX curve.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FbxMaterialProperty {
+    /// Base color, in linear RGB
+    BaseColor,
+    /// Emissive color, in linear RGB
+    EmissiveColor,
+    /// Translation of the base color texture coordinates
+    UvOffset,
+    /// Scale of the base color texture coordinates
+    UvScale,
+    /// Rotation of the base color texture coordinates, in radians
+    UvRotation,
+}
+
+/// Animated property of a single material.
+#[derive(Debug, Clone)]
+pub struct FbxMaterialTrack {
+    pub material_index: usize,
+    pub target: AnimationTargetId,
+    pub property: FbxMaterialProperty,
+    /// Keys in Bevy units, scalar properties use the `x` component and UV
+    /// properties the `x` and `y` components
+    pub keyframes: FbxKeyframes<Vec3>,
+}
+
+/// Animation converted from an FBX animation stack.
+#[derive(Debug, Clone)]
+pub struct FbxAnimation {
+    pub name: String,
+    pub duration: f32,
+    pub tracks: Vec<FbxNodeTrack>,
+    pub morph_tracks: Vec<FbxMorphTrack>,
+    pub property_tracks: Vec<FbxPropertyTrack>,
+    pub material_tracks: Vec<FbxMaterialTrack>,
+    /// Motion moved out of the root joint, if root motion is extracted
+    pub root_motion: Option<FbxRootMotion>,
+    pub markers: Vec<FbxAnimationMarker>,
+}
+
+/// Timeline marker of an animation, with its time in seconds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FbxAnimationMarker {
+    pub time: f32,
+    pub name: String,
+    pub payload: Option<String>,
+}
+
+/// Event triggered on the [`AnimationPlayer`](bevy::animation::AnimationPlayer)
+/// entity when playback crosses an FBX timeline marker.
+#[derive(AnimationEvent, Debug, Clone, PartialEq)]
+pub struct FbxAnimationEvent {
+    pub name: String,
+    pub payload: Option<String>,
+}
+
+/// Horizontal motion of a root joint, moved out of an animation so that it
+/// plays in place.
+///
+/// Translation is the XZ offset and rotation the yaw from the first frame,
+/// both in the Y-up space of the scene.
+#[derive(Asset, Debug, Clone, TypePath)]
+pub struct FbxRootMotion {
+    pub node_index: usize,
+    pub duration: f32,
+    pub translation: FbxKeyframes<Vec3>,
+    pub rotation: FbxKeyframes<Quat>,
+}
+
+/// Rest pose of a single bone, used to retarget animations between rigs.
+#[derive(Debug, Clone)]
+pub struct FbxRestBone {
+    pub target: AnimationTargetId,
+    pub parent: Option<String>,
+    /// Rest transform relative to the parent
+    pub local: Transform,
+    /// Rest transform of the parent in world space
+    pub parent_world: Mat4,
+    /// Rest position in world space
+    pub world_translation: Vec3,
+}
+
+/// Named clip cut out of an animation stack by frame range.
+#[derive(Debug, Clone)]
+pub struct FbxAnimationClip {
+    pub name: Box<str>,
+    pub clip: Handle<AnimationClip>,
+    /// Whether the clip is meant to play on repeat
+    pub looping: bool,
+    pub root_motion: Option<Handle<FbxRootMotion>>,
+}
+
 // ============================================================================
 // Main FBX Asset
 // ============================================================================
@@ -186,10 +590,38 @@ pub struct Fbx {
     pub named_materials: HashMap<Box<str>, Handle<StandardMaterial>>,
     pub nodes: Vec<Handle<FbxNode>>,
     pub named_nodes: HashMap<Box<str>, Handle<FbxNode>>,
+    /// Joint hierarchy of each chain of bones
+    pub skeletons: Vec<Handle<Skeleton>>,
     pub skins: Vec<Handle<FbxSkin>>,
     pub named_skins: HashMap<Box<str>, Handle<FbxSkin>>,
+    pub humanoids: Vec<Handle<FbxHumanoid>>,
+    /// Skinning which the converted meshes do not reproduce exactly
+    pub skinning_warnings: Vec<FbxSkinningWarning>,
+    pub animations: Vec<Handle<AnimationClip>>,
+    pub named_animations: HashMap<Box<str>, Handle<AnimationClip>>,
+    pub animation_graph: Option<Handle<AnimationGraph>>,
+    pub animation_graph_nodes: Vec<AnimationNodeIndex>,
+    pub animation_stacks: Vec<Handle<AnimationGraph>>,
+    pub named_animation_stacks: HashMap<Box<str>, Handle<AnimationGraph>>,
+    pub animation_clips: Vec<FbxAnimationClip>,
+    /// Root motion of each animation in `animations`, if extracted
+    pub root_motions: Vec<Option<Handle<FbxRootMotion>>>,
+    /// Vertex animations read from the geometry caches of meshes
+    pub vertex_animations: Vec<Handle<FbxVertexAnimation>>,
+    /// Animated nodes without a node of the same name in the target skeleton
+    pub unmatched_bones: Vec<String>,
+    /// Node keys before and after keyframe reduction, if enabled
+    pub key_reduction: Option<FbxKeyReduction>,
+    /// Hierarchy and curves used by [`Fbx::sample_pose`]
+    pub poses: FbxPoseData,
     pub default_scene: Option<Handle<WorldAsset>>,
+    /// Axes of the converted scene data, always Y up and right-handed. The
+    /// axes of the file are in `scene_settings`.
     pub axis_system: FbxAxisSystem,
+    /// Length of a unit of the converted scene data in meters, always 1.0.
+    /// The unit of the file is in `scene_settings`.
     pub unit_scale: f32,
+    /// Timeline, axes and units stored in the file
+    pub scene_settings: FbxSceneSettings,
     pub metadata: FbxMeta,
 }
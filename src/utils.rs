@@ -1,10 +1,20 @@
 //! Utility functions for converting between ufbx and Bevy types.
 
+use crate::types::{
+    FbxAxisSystem, FbxInterpolation, FbxSceneSettings, FbxSkinningMethod, FbxTimeMode, Handedness,
+};
 use bevy::math::{Affine2, Mat4};
 use bevy::prelude::*;
 
 /// Convert ufbx texture UV transform to Bevy Affine2.
 pub fn convert_texture_uv_transform(texture: &ufbx::Texture) -> Affine2 {
+    let (scale, rotation_z, translation) = convert_texture_uv_parts(texture);
+    Affine2::from_scale_angle_translation(scale, rotation_z, translation)
+}
+
+/// Split ufbx texture UV transform into the scale, rotation and translation
+/// used by [`convert_texture_uv_transform`].
+pub fn convert_texture_uv_parts(texture: &ufbx::Texture) -> (Vec2, f32, Vec2) {
     let translation = Vec2::new(
         texture.uv_transform.translation.x as f32,
         texture.uv_transform.translation.y as f32,
@@ -14,7 +24,7 @@ pub fn convert_texture_uv_transform(texture: &ufbx::Texture) -> Affine2 {
         texture.uv_transform.scale.y as f32,
     );
     let rotation_z = texture.uv_transform.rotation.z as f32;
-    Affine2::from_scale_angle_translation(scale, rotation_z, translation)
+    (scale, rotation_z, translation)
 }
 
 /// Convert ufbx matrix to Bevy Mat4.
@@ -39,6 +49,16 @@ pub fn convert_matrix(m: &ufbx::Matrix) -> Mat4 {
     ])
 }
 
+/// Convert ufbx vector to Bevy Vec3.
+pub fn convert_vec3(v: &ufbx::Vec3) -> Vec3 {
+    Vec3::new(v.x as f32, v.y as f32, v.z as f32)
+}
+
+/// Convert ufbx quaternion to Bevy Quat.
+pub fn convert_quat(q: &ufbx::Quat) -> Quat {
+    Quat::from_xyzw(q.x as f32, q.y as f32, q.z as f32, q.w as f32)
+}
+
 /// Convert ufbx transform to Bevy Transform.
 pub fn convert_transform(t: &ufbx::Transform) -> Transform {
     Transform {
@@ -56,3 +76,116 @@ pub fn convert_transform(t: &ufbx::Transform) -> Transform {
         scale: Vec3::new(t.scale.x as f32, t.scale.y as f32, t.scale.z as f32),
     }
 }
+
+/// Convert ufbx keyframe interpolation to FbxInterpolation.
+///
+/// Returns `None` for `ConstantNext`, which jumps to the next value at the
+/// start of a segment and has no Bevy curve equivalent.
+pub fn convert_interpolation(interpolation: ufbx::Interpolation) -> Option<FbxInterpolation> {
+    match interpolation {
+        ufbx::Interpolation::ConstantPrev => Some(FbxInterpolation::Constant),
+        ufbx::Interpolation::ConstantNext => None,
+        ufbx::Interpolation::Linear => Some(FbxInterpolation::Linear),
+        ufbx::Interpolation::Cubic => Some(FbxInterpolation::Cubic),
+    }
+}
+
+/// Convert ufbx coordinate axis to a Bevy unit vector, `None` if unknown.
+pub fn convert_axis(axis: ufbx::CoordinateAxis) -> Option<Vec3> {
+    match axis {
+        ufbx::CoordinateAxis::PositiveX => Some(Vec3::X),
+        ufbx::CoordinateAxis::NegativeX => Some(Vec3::NEG_X),
+        ufbx::CoordinateAxis::PositiveY => Some(Vec3::Y),
+        ufbx::CoordinateAxis::NegativeY => Some(Vec3::NEG_Y),
+        ufbx::CoordinateAxis::PositiveZ => Some(Vec3::Z),
+        ufbx::CoordinateAxis::NegativeZ => Some(Vec3::NEG_Z),
+        ufbx::CoordinateAxis::Unknown => None,
+    }
+}
+
+/// Convert ufbx coordinate axes to FbxAxisSystem.
+///
+/// Unknown axes fall back to the FBX defaults of X right, Y up and Z front.
+pub fn convert_axes(axes: &ufbx::CoordinateAxes) -> FbxAxisSystem {
+    let right = convert_axis(axes.right).unwrap_or(Vec3::X);
+    let up = convert_axis(axes.up).unwrap_or(Vec3::Y);
+    let front = convert_axis(axes.front).unwrap_or(Vec3::Z);
+    let handedness = if right.cross(up).dot(front) > 0.0 {
+        Handedness::Right
+    } else {
+        Handedness::Left
+    };
+    FbxAxisSystem {
+        up,
+        front,
+        handedness,
+    }
+}
+
+/// Convert ufbx time mode to FbxTimeMode.
+pub fn convert_time_mode(time_mode: ufbx::TimeMode) -> FbxTimeMode {
+    match time_mode {
+        ufbx::TimeMode::Default => FbxTimeMode::Default,
+        ufbx::TimeMode::E120Fps => FbxTimeMode::Fps120,
+        ufbx::TimeMode::E100Fps => FbxTimeMode::Fps100,
+        ufbx::TimeMode::E60Fps => FbxTimeMode::Fps60,
+        ufbx::TimeMode::E50Fps => FbxTimeMode::Fps50,
+        ufbx::TimeMode::E48Fps => FbxTimeMode::Fps48,
+        ufbx::TimeMode::E30Fps => FbxTimeMode::Fps30,
+        ufbx::TimeMode::E30FpsDrop => FbxTimeMode::Fps30Drop,
+        ufbx::TimeMode::NtscDropFrame => FbxTimeMode::NtscDropFrame,
+        ufbx::TimeMode::NtscFullFrame => FbxTimeMode::NtscFullFrame,
+        ufbx::TimeMode::Pal => FbxTimeMode::Pal,
+        ufbx::TimeMode::E24Fps => FbxTimeMode::Fps24,
+        ufbx::TimeMode::E1000Fps => FbxTimeMode::Fps1000,
+        ufbx::TimeMode::FilmFullFrame => FbxTimeMode::FilmFullFrame,
+        ufbx::TimeMode::Custom => FbxTimeMode::Custom,
+        ufbx::TimeMode::E96Fps => FbxTimeMode::Fps96,
+        ufbx::TimeMode::E72Fps => FbxTimeMode::Fps72,
+        ufbx::TimeMode::E5994Fps => FbxTimeMode::Fps59_94,
+    }
+}
+
+/// Convert ufbx skinning method to FbxSkinningMethod.
+pub fn convert_skinning_method(method: ufbx::SkinningMethod) -> FbxSkinningMethod {
+    match method {
+        ufbx::SkinningMethod::Linear => FbxSkinningMethod::Linear,
+        ufbx::SkinningMethod::Rigid => FbxSkinningMethod::Rigid,
+        ufbx::SkinningMethod::DualQuaternion => FbxSkinningMethod::DualQuaternion,
+        ufbx::SkinningMethod::BlendedDqLinear => FbxSkinningMethod::BlendedDqLinear,
+    }
+}
+
+/// Convert the settings of a ufbx scene to FbxSceneSettings.
+///
+/// The timeline span is stored in `KTime` ticks, converted to seconds.
+pub fn convert_scene_settings(scene: &ufbx::Scene) -> FbxSceneSettings {
+    let settings = &scene.settings;
+    let ticks_per_second = scene.metadata.ktime_second.max(1) as f64;
+    let time = |name: &str| {
+        settings
+            .props
+            .find_prop(name)
+            .map_or(0.0, |prop| prop.value_int as f64 / ticks_per_second)
+    };
+
+    let axes = convert_axes(&settings.axes);
+
+    FbxSceneSettings {
+        time_mode: convert_time_mode(settings.time_mode),
+        frames_per_second: settings.frames_per_second,
+        time_start: time("TimeSpanStart"),
+        time_stop: time("TimeSpanStop"),
+        axes,
+        original_up: convert_axis(settings.axes.up),
+        original_front: convert_axis(settings.axes.front),
+        handedness: axes.handedness,
+        unit_meters: settings.unit_meters as f32,
+        original_unit_meters: settings.original_unit_meters as f32,
+        ambient_color: Color::srgb(
+            settings.ambient_color.x as f32,
+            settings.ambient_color.y as f32,
+            settings.ambient_color.z as f32,
+        ),
+    }
+}
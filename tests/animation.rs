@@ -0,0 +1,168 @@
+//! Tests for animation conversion helpers.
+
+use bevy::animation::AnimationTargetId;
+use bevy::prelude::*;
+use bevy_ufbx::animation::{
+    make_rotations_continuous, sample_cubic_keyframes, sample_keyframes, slice_animation,
+};
+use bevy_ufbx::types::{
+    FbxAnimation, FbxAnimationMarker, FbxInterpolation, FbxKeyframes, FbxNodeTrack,
+};
+
+#[test]
+fn test_rotations_continuous_flips_opposite_hemisphere() {
+    let q = Quat::from_rotation_y(0.5);
+    let mut rotations = vec![q, -q, Quat::from_rotation_y(0.6)];
+
+    make_rotations_continuous(&mut rotations);
+
+    assert_eq!(rotations[1], q);
+    for pair in rotations.windows(2) {
+        assert!(pair[0].dot(pair[1]) >= 0.0);
+    }
+}
+
+#[test]
+fn test_rotations_continuous_keeps_same_hemisphere() {
+    let original = vec![
+        Quat::IDENTITY,
+        Quat::from_rotation_x(0.1),
+        Quat::from_rotation_x(0.2),
+    ];
+    let mut rotations = original.clone();
+
+    make_rotations_continuous(&mut rotations);
+
+    assert_eq!(rotations, original);
+}
+
+#[test]
+fn test_sample_keyframes_linear_and_clamped() {
+    let keyframes = FbxKeyframes {
+        times: vec![0.0, 1.0],
+        values: vec![0.0_f32, 2.0],
+        ..Default::default()
+    };
+
+    assert_eq!(sample_keyframes(&keyframes, -1.0), Some(0.0));
+    assert_eq!(sample_keyframes(&keyframes, 0.5), Some(1.0));
+    assert_eq!(sample_keyframes(&keyframes, 2.0), Some(2.0));
+}
+
+#[test]
+fn test_sample_keyframes_stepped() {
+    let keyframes = FbxKeyframes {
+        interpolation: FbxInterpolation::Constant,
+        times: vec![0.0, 1.0],
+        values: vec![0.0_f32, 2.0],
+        ..Default::default()
+    };
+
+    assert_eq!(sample_keyframes(&keyframes, 0.9), Some(0.0));
+    assert_eq!(sample_keyframes(&keyframes, 1.0), Some(2.0));
+    assert_eq!(sample_keyframes(&FbxKeyframes::<f32>::default(), 0.0), None);
+}
+
+#[test]
+fn test_slice_animation_adds_boundary_keys() {
+    let animation = FbxAnimation {
+        name: "Take".to_string(),
+        duration: 2.0,
+        tracks: vec![FbxNodeTrack {
+            node_index: 1,
+            target: AnimationTargetId::from_name(&Name::new("Hips")),
+            translation: FbxKeyframes {
+                times: vec![0.0, 1.0, 2.0],
+                values: vec![Vec3::ZERO, Vec3::X, Vec3::X * 2.0],
+                ..Default::default()
+            },
+            rotation: FbxKeyframes {
+                times: vec![0.0],
+                values: vec![Quat::IDENTITY],
+                ..Default::default()
+            },
+            scale: FbxKeyframes::default(),
+        }],
+        morph_tracks: Vec::new(),
+        property_tracks: Vec::new(),
+        material_tracks: Vec::new(),
+        root_motion: None,
+        markers: vec![
+            FbxAnimationMarker {
+                time: 1.0,
+                name: "Footstep".to_string(),
+                payload: Some("left".to_string()),
+            },
+            FbxAnimationMarker {
+                time: 1.8,
+                name: "Footstep".to_string(),
+                payload: Some("right".to_string()),
+            },
+        ],
+    };
+
+    let clip = slice_animation(&animation, "Walk", 0.5, 1.5);
+
+    assert_eq!(clip.name, "Walk");
+    assert_eq!(clip.duration, 1.0);
+    let translation = &clip.tracks[0].translation;
+    assert_eq!(translation.times, vec![0.0, 0.5, 1.0]);
+    assert_eq!(
+        translation.values,
+        vec![Vec3::X * 0.5, Vec3::X, Vec3::X * 1.5]
+    );
+    assert_eq!(clip.tracks[0].rotation.values, vec![Quat::IDENTITY]);
+
+    assert_eq!(clip.markers.len(), 1);
+    assert_eq!(clip.markers[0].time, 0.5);
+    assert_eq!(clip.markers[0].payload.as_deref(), Some("left"));
+}
+
+#[test]
+fn test_slice_animation_splits_cubic_segments() {
+    // Leaves the first key at a slope of 2, the second key flat
+    let translation = FbxKeyframes {
+        interpolation: FbxInterpolation::Cubic,
+        times: vec![0.0, 1.0, 2.0],
+        values: vec![Vec3::ZERO, Vec3::X, Vec3::X],
+        tangents: vec![
+            (Vec3::X * 2.0, Vec3::X * 2.0),
+            (Vec3::ZERO, Vec3::ZERO),
+            (Vec3::ZERO, Vec3::ZERO),
+        ],
+    };
+    let (value, slope) = sample_cubic_keyframes(&translation, 0.5).unwrap();
+    assert!(value.abs_diff_eq(Vec3::X * 0.75, 1e-6));
+    assert!(slope.abs_diff_eq(Vec3::X, 1e-6));
+
+    let animation = FbxAnimation {
+        name: "Take".to_string(),
+        duration: 2.0,
+        tracks: vec![FbxNodeTrack {
+            node_index: 1,
+            target: AnimationTargetId::from_name(&Name::new("Hips")),
+            translation,
+            rotation: FbxKeyframes::default(),
+            scale: FbxKeyframes::default(),
+        }],
+        morph_tracks: Vec::new(),
+        property_tracks: Vec::new(),
+        material_tracks: Vec::new(),
+        root_motion: None,
+        markers: Vec::new(),
+    };
+
+    let clip = slice_animation(&animation, "Cut", 0.5, 2.0);
+
+    let translation = &clip.tracks[0].translation;
+    assert_eq!(translation.interpolation, FbxInterpolation::Cubic);
+    assert_eq!(translation.times, vec![0.0, 0.5, 1.5]);
+    assert!(translation.values[0].abs_diff_eq(value, 1e-6));
+    assert!(translation.tangents[0].1.abs_diff_eq(slope, 1e-6));
+    // The cut follows the source curve between the remaining keys
+    for time in [0.6, 0.8, 0.9] {
+        let (expected, _) = sample_cubic_keyframes(&animation.tracks[0].translation, time).unwrap();
+        let (sampled, _) = sample_cubic_keyframes(translation, time - 0.5).unwrap();
+        assert!(sampled.abs_diff_eq(expected, 1e-5));
+    }
+}
@@ -0,0 +1,200 @@
+//! Tests for FBX constraint evaluation.
+
+use bevy::prelude::*;
+use bevy_ufbx::constraint::{
+    aim_rotation, bake_constraints, node_constraints, FbxAimUp, FbxConstraint, FbxConstraintKind,
+    FbxConstraintTarget,
+};
+use bevy_ufbx::scene::spawn_constraints;
+use bevy_ufbx::types::FbxAnimation;
+
+/// A node following two position constraints, to "First" and then "Second".
+const TWO_CONSTRAINTS: &str = r#"; FBX 7.4.0 project file
+FBXHeaderExtension:  {
+	FBXHeaderVersion: 1003
+	FBXVersion: 7400
+}
+Objects:  {
+	Model: 1001, "Model::Follower", "Null" {
+		Version: 232
+	}
+	Model: 1002, "Model::First", "Null" {
+		Version: 232
+		Properties70:  {
+			P: "Lcl Translation", "Lcl Translation", "", "A",1,0,0
+		}
+	}
+	Model: 1003, "Model::Second", "Null" {
+		Version: 232
+		Properties70:  {
+			P: "Lcl Translation", "Lcl Translation", "", "A",0,0,5
+		}
+	}
+	Constraint: 2001, "Constraint::FollowFirst", "Position From Positions" {
+		Type: "Position From Positions"
+		Properties70:  {
+			P: "Active", "bool", "", "",1
+			P: "Weight", "Number", "", "A",100
+			P: "First.Weight", "Number", "", "A",100
+		}
+	}
+	Constraint: 2002, "Constraint::FollowSecond", "Position From Positions" {
+		Type: "Position From Positions"
+		Properties70:  {
+			P: "Active", "bool", "", "",1
+			P: "Weight", "Number", "", "A",100
+			P: "Second.Weight", "Number", "", "A",100
+		}
+	}
+	AnimationStack: 3001, "AnimStack::Take", "" {
+		Properties70:  {
+			P: "LocalStop", "KTime", "Time", "",46186158000
+		}
+	}
+	AnimationLayer: 3002, "AnimLayer::Base", "" {
+	}
+}
+Connections:  {
+	C: "OO",1001,0
+	C: "OO",1002,0
+	C: "OO",1003,0
+	C: "OO",2001,0
+	C: "OO",2002,0
+	C: "OP",1001,2001, "Constrained Object"
+	C: "OP",1002,2001, "Source"
+	C: "OP",1001,2002, "Constrained Object"
+	C: "OP",1003,2002, "Source"
+	C: "OO",3002,3001
+}
+"#;
+
+fn constraint(kind: FbxConstraintKind, targets: &[f32]) -> FbxConstraint {
+    FbxConstraint {
+        kind,
+        weight: 1.0,
+        targets: targets
+            .iter()
+            .map(|&weight| FbxConstraintTarget {
+                entity: Entity::PLACEHOLDER,
+                weight,
+                offset: Transform::IDENTITY,
+            })
+            .collect(),
+        translation_axes: [true; 3],
+        rotation_axes: [true; 3],
+        scale_axes: [true; 3],
+        offset: Transform::IDENTITY,
+        aim_vector: Vec3::X,
+        up_vector: Vec3::Y,
+        aim_up: FbxAimUp::Vector(Vec3::Y),
+        up_node: None,
+    }
+}
+
+#[test]
+fn test_position_constraint() {
+    let parent = Transform::from_xyz(1.0, 0.0, 0.0);
+    let targets = [
+        Transform::from_xyz(2.0, 0.0, 0.0),
+        Transform::from_xyz(2.0, 4.0, 0.0),
+    ];
+
+    let mut position = constraint(FbxConstraintKind::Position, &[1.0, 1.0]);
+    let local = position.apply(&Transform::IDENTITY, &parent, &targets, None);
+    assert!(local
+        .translation
+        .abs_diff_eq(Vec3::new(1.0, 2.0, 0.0), 1e-5));
+
+    // Unconstrained axes and the constraint weight keep the animated value
+    position.translation_axes = [true, false, true];
+    position.weight = 0.5;
+    let local = position.apply(&Transform::IDENTITY, &parent, &targets, None);
+    assert!(local
+        .translation
+        .abs_diff_eq(Vec3::new(0.5, 0.0, 0.0), 1e-5));
+
+    // Without weighted targets the node is left alone
+    let position = constraint(FbxConstraintKind::Position, &[0.0, 0.0]);
+    let animated = Transform::from_xyz(0.0, 0.0, 3.0);
+    assert_eq!(position.apply(&animated, &parent, &targets, None), animated);
+}
+
+#[test]
+fn test_parent_constraint() {
+    let mut parent_constraint = constraint(FbxConstraintKind::Parent, &[1.0]);
+    parent_constraint.targets[0].offset = Transform::from_xyz(0.0, 1.0, 0.0);
+    let target = Transform::from_xyz(5.0, 0.0, 0.0)
+        .with_rotation(Quat::from_rotation_z(std::f32::consts::FRAC_PI_2));
+
+    let local =
+        parent_constraint.apply(&Transform::IDENTITY, &Transform::IDENTITY, &[target], None);
+    assert!(local
+        .translation
+        .abs_diff_eq(Vec3::new(4.0, 0.0, 0.0), 1e-5));
+    assert!(local.rotation.abs_diff_eq(target.rotation, 1e-5));
+}
+
+#[test]
+fn test_aim_constraint() {
+    let aim = constraint(FbxConstraintKind::Aim, &[1.0]);
+    let target = Transform::from_xyz(0.0, 0.0, -3.0);
+
+    let local = aim.apply(&Transform::IDENTITY, &Transform::IDENTITY, &[target], None);
+    assert!((local.rotation * Vec3::X).abs_diff_eq(Vec3::NEG_Z, 1e-5));
+    assert!((local.rotation * Vec3::Y).abs_diff_eq(Vec3::Y, 1e-5));
+
+    // The up axis is twisted towards the world up vector
+    let rotation = aim_rotation(Vec3::Z, Vec3::Y, Vec3::X, Some(Vec3::Z));
+    assert!((rotation * Vec3::Z).abs_diff_eq(Vec3::X, 1e-5));
+    assert!((rotation * Vec3::Y).abs_diff_eq(Vec3::Z, 1e-5));
+}
+
+#[test]
+fn test_first_constraint_of_a_node_wins() {
+    let scene = ufbx::load_memory(TWO_CONSTRAINTS.as_bytes(), ufbx::LoadOpts::default())
+        .expect("Failed to load constraints");
+    let node_index = |name: &str| {
+        scene
+            .nodes
+            .iter()
+            .position(|node| node.element.name.as_ref() == name)
+            .unwrap()
+    };
+
+    let constraints = node_constraints(&scene, |_| Entity::PLACEHOLDER);
+    assert_eq!(constraints.len(), 1);
+    assert_eq!(constraints[0].0.element.name.as_ref(), "FollowFirst");
+
+    // Baking follows the first constraint
+    let mut animation = FbxAnimation {
+        name: "Take".to_string(),
+        duration: 0.0,
+        tracks: Vec::new(),
+        morph_tracks: Vec::new(),
+        property_tracks: Vec::new(),
+        material_tracks: Vec::new(),
+        root_motion: None,
+        markers: Vec::new(),
+    };
+    bake_constraints(&scene, &scene.anim_stacks[0], &mut animation, 30.0).unwrap();
+    assert_eq!(animation.tracks.len(), 1);
+    assert_eq!(animation.tracks[0].node_index, node_index("Follower"));
+    assert!(animation.tracks[0].translation.values[0].abs_diff_eq(Vec3::X, 1e-5));
+
+    // And so do the constraints evaluated at runtime
+    let mut world = World::new();
+    let node_entities: Vec<Entity> = scene
+        .nodes
+        .iter()
+        .map(|_| world.spawn(Transform::default()).id())
+        .collect();
+    spawn_constraints(&scene, &mut world, &node_entities);
+    let constraint = world
+        .get::<FbxConstraint>(node_entities[node_index("Follower")])
+        .unwrap();
+    assert_eq!(constraint.targets.len(), 1);
+    assert_eq!(
+        constraint.targets[0].entity,
+        node_entities[node_index("First")]
+    );
+}
@@ -0,0 +1,185 @@
+//! Tests for animation events from FBX markers.
+
+use bevy::animation::{AnimationPlayer, AnimationPlugin};
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use bevy_ufbx::animation::create_animation_clip;
+use bevy_ufbx::events::{collect_markers, marker_name, parse_marker_list};
+use bevy_ufbx::types::{FbxAnimation, FbxAnimationEvent, FbxAnimationMarker};
+use std::time::Duration;
+
+/// A footstep event property keyed at one second by both layers of a stack.
+const FOOTSTEPS: &str = r#"; FBX 7.4.0 project file
+FBXHeaderExtension:  {
+	FBXHeaderVersion: 1003
+	FBXVersion: 7400
+}
+Objects:  {
+	Model: 1001, "Model::Root", "Null" {
+		Version: 232
+		Properties70:  {
+			P: "Event_Footstep", "Number", "", "A+U",0
+		}
+	}
+	AnimationStack: 3001, "AnimStack::Walk", "" {
+	}
+	AnimationLayer: 3002, "AnimLayer::Base", "" {
+	}
+	AnimationLayer: 3003, "AnimLayer::Override", "" {
+	}
+	AnimationCurveNode: 3004, "AnimCurveNode::Event_Footstep", "" {
+		Properties70:  {
+			P: "d|Event_Footstep", "Number", "", "A",0
+		}
+	}
+	AnimationCurve: 3005, "AnimCurve::", "" {
+		KeyTime: *2 {
+			a: 0,46186158000
+		}
+		KeyValueFloat: *2 {
+			a: 0,1
+		}
+		KeyAttrFlags: *1 {
+			a: 4
+		}
+		KeyAttrDataFloat: *4 {
+			a: 0,0,0,0
+		}
+		KeyAttrRefCount: *1 {
+			a: 2
+		}
+	}
+	AnimationCurveNode: 3006, "AnimCurveNode::Event_Footstep", "" {
+		Properties70:  {
+			P: "d|Event_Footstep", "Number", "", "A",0
+		}
+	}
+	AnimationCurve: 3007, "AnimCurve::", "" {
+		KeyTime: *2 {
+			a: 0,46186158000
+		}
+		KeyValueFloat: *2 {
+			a: 0,1
+		}
+		KeyAttrFlags: *1 {
+			a: 4
+		}
+		KeyAttrDataFloat: *4 {
+			a: 0,0,0,0
+		}
+		KeyAttrRefCount: *1 {
+			a: 2
+		}
+	}
+}
+Connections:  {
+	C: "OO",1001,0
+	C: "OO",3002,3001
+	C: "OO",3003,3001
+	C: "OO",3004,3002
+	C: "OP",3004,1001, "Event_Footstep"
+	C: "OP",3005,3004, "d|Event_Footstep"
+	C: "OO",3006,3003
+	C: "OP",3006,1001, "Event_Footstep"
+	C: "OP",3007,3006, "d|Event_Footstep"
+}
+"#;
+
+#[test]
+fn test_marker_name_strips_prefix() {
+    assert_eq!(marker_name("Event_Footstep", "Event"), Some("Footstep"));
+    assert_eq!(marker_name("EventHit", "Event"), Some("Hit"));
+    assert_eq!(marker_name("Event", "Event"), Some("Event"));
+    assert_eq!(marker_name("Footstep", "Event"), None);
+}
+
+#[test]
+fn test_parse_marker_list() {
+    let markers = parse_marker_list("12:left; 40 : right ;55;oops:1");
+
+    assert_eq!(
+        markers,
+        vec![
+            (12.0, Some("left".to_string())),
+            (40.0, Some("right".to_string())),
+            (55.0, None),
+        ]
+    );
+}
+
+#[test]
+fn test_collect_markers_once_across_layers() {
+    let scene = ufbx::load_memory(FOOTSTEPS.as_bytes(), ufbx::LoadOpts::default())
+        .expect("Failed to load footsteps");
+    let stack = &scene.anim_stacks[0];
+    assert_eq!(stack.layers.len(), 2);
+
+    let markers = collect_markers(stack, 30.0, "Event");
+
+    assert_eq!(
+        markers,
+        vec![FbxAnimationMarker {
+            time: 1.0,
+            name: "Footstep".to_string(),
+            payload: None,
+        }]
+    );
+    assert!(collect_markers(stack, 30.0, "").is_empty());
+}
+
+#[derive(Resource, Default)]
+struct FiredEvents(Vec<FbxAnimationEvent>);
+
+#[test]
+fn test_markers_trigger_animation_events() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), AnimationPlugin))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+            250,
+        )))
+        .init_resource::<FiredEvents>()
+        .add_observer(
+            |event: On<FbxAnimationEvent>, mut fired: ResMut<FiredEvents>| {
+                fired.0.push(event.event().clone());
+            },
+        );
+
+    let animation = FbxAnimation {
+        name: "Walk".to_string(),
+        duration: 1.0,
+        tracks: Vec::new(),
+        morph_tracks: Vec::new(),
+        property_tracks: Vec::new(),
+        material_tracks: Vec::new(),
+        root_motion: None,
+        markers: vec![FbxAnimationMarker {
+            time: 0.4,
+            name: "Footstep".to_string(),
+            payload: Some("left".to_string()),
+        }],
+    };
+    let clip = app
+        .world_mut()
+        .resource_mut::<Assets<AnimationClip>>()
+        .add(create_animation_clip(&animation));
+    let (graph, node) = AnimationGraph::from_clip(clip);
+    let graph = app
+        .world_mut()
+        .resource_mut::<Assets<AnimationGraph>>()
+        .add(graph);
+    let mut player = AnimationPlayer::default();
+    player.play(node);
+    app.world_mut().spawn((player, AnimationGraphHandle(graph)));
+
+    for _ in 0..4 {
+        app.update();
+    }
+
+    assert_eq!(
+        app.world().resource::<FiredEvents>().0,
+        vec![FbxAnimationEvent {
+            name: "Footstep".to_string(),
+            payload: Some("left".to_string()),
+        }]
+    );
+}
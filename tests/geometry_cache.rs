@@ -0,0 +1,65 @@
+//! Tests for vertex animation from geometry caches.
+
+use bevy::prelude::*;
+use bevy_ufbx::geometry_cache::{read_geometry_cache, FbxVertexAnimation};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// 3ds Max point cache with two points over `frames` samples, moving along X.
+fn point_cache(frames: usize) -> Vec<u8> {
+    let mut bytes = b"POINTCACHE2\0".to_vec();
+    bytes.extend(1i32.to_le_bytes());
+    bytes.extend(2i32.to_le_bytes());
+    bytes.extend(0f32.to_le_bytes());
+    bytes.extend(1f32.to_le_bytes());
+    bytes.extend((frames as i32).to_le_bytes());
+    for frame in 0..frames {
+        for point in 0..2 {
+            for value in [frame as f32, point as f32, 0.0] {
+                bytes.extend(value.to_le_bytes());
+            }
+        }
+    }
+    bytes
+}
+
+#[test]
+fn test_read_point_cache() {
+    let files = HashMap::from([("sim/cloth.pc2".to_string(), Arc::from(point_cache(3)))]);
+    let mut missing = Vec::new();
+
+    let animations = read_geometry_cache("sim/cloth.pc2", &files, 30.0, &mut missing).unwrap();
+
+    assert!(missing.is_empty());
+    assert_eq!(animations.len(), 1);
+    let animation = &animations[0];
+    assert_eq!(animation.frames.len(), 3);
+    assert!((animation.duration() - 2.0 / 30.0).abs() < 1e-5);
+    assert_eq!(
+        animation.frames[2],
+        vec![Vec3::new(2.0, 0.0, 0.0), Vec3::new(2.0, 1.0, 0.0)]
+    );
+}
+
+#[test]
+fn test_missing_cache_file() {
+    let mut missing = Vec::new();
+
+    let result = read_geometry_cache("sim/cloth.pc2", &HashMap::new(), 30.0, &mut missing);
+
+    assert!(result.is_err());
+    assert_eq!(missing, vec!["sim/cloth.pc2".to_string()]);
+}
+
+#[test]
+fn test_sample_vertex_animation() {
+    let animation = FbxVertexAnimation {
+        name: "cloth".to_string(),
+        times: vec![0.0, 1.0],
+        frames: vec![vec![Vec3::ZERO], vec![Vec3::new(2.0, 0.0, 0.0)]],
+    };
+
+    assert_eq!(animation.sample(0.25), vec![Vec3::new(0.5, 0.0, 0.0)]);
+    assert_eq!(animation.sample(-1.0), vec![Vec3::ZERO]);
+    assert_eq!(animation.sample(3.0), vec![Vec3::new(2.0, 0.0, 0.0)]);
+}
@@ -0,0 +1,72 @@
+//! Tests for humanoid bone classification.
+
+use bevy_ufbx::humanoid::classify_bone;
+use bevy_ufbx::types::{HumanoidBone, HumanoidFinger, HumanoidNaming, HumanoidSide};
+
+#[test]
+fn test_classify_mixamo_bones() {
+    assert_eq!(
+        classify_bone("mixamorig:Hips"),
+        Some((HumanoidNaming::Mixamo, HumanoidBone::Hips))
+    );
+    assert_eq!(
+        classify_bone("mixamorig:LeftForeArm"),
+        Some((
+            HumanoidNaming::Mixamo,
+            HumanoidBone::LowerArm(HumanoidSide::Left)
+        ))
+    );
+    assert_eq!(
+        classify_bone("mixamorig:RightHandPinky2"),
+        Some((
+            HumanoidNaming::Mixamo,
+            HumanoidBone::Finger(HumanoidSide::Right, HumanoidFinger::Little, 2)
+        ))
+    );
+    // Finger tip end bones are not part of the humanoid
+    assert_eq!(classify_bone("mixamorig:RightHandPinky4"), None);
+}
+
+#[test]
+fn test_classify_human_ik_bones() {
+    assert_eq!(
+        classify_bone("Character1_LeftUpLeg"),
+        Some((
+            HumanoidNaming::HumanIk,
+            HumanoidBone::UpperLeg(HumanoidSide::Left)
+        ))
+    );
+    assert_eq!(
+        classify_bone("Spine1"),
+        Some((HumanoidNaming::HumanIk, HumanoidBone::Chest))
+    );
+}
+
+#[test]
+fn test_classify_biped_bones() {
+    assert_eq!(
+        classify_bone("Bip01 Pelvis"),
+        Some((HumanoidNaming::Biped, HumanoidBone::Hips))
+    );
+    assert_eq!(
+        classify_bone("Bip001 R Calf"),
+        Some((
+            HumanoidNaming::Biped,
+            HumanoidBone::LowerLeg(HumanoidSide::Right)
+        ))
+    );
+    assert_eq!(
+        classify_bone("Bip01 L Finger02"),
+        Some((
+            HumanoidNaming::Biped,
+            HumanoidBone::Finger(HumanoidSide::Left, HumanoidFinger::Thumb, 3)
+        ))
+    );
+    assert_eq!(classify_bone("Bip01"), None);
+}
+
+#[test]
+fn test_classify_unknown_bones() {
+    assert_eq!(classify_bone("Tail_01"), None);
+    assert_eq!(classify_bone("LeftEye"), None);
+}
@@ -44,12 +44,54 @@ fn test_animation_label() {
     assert_eq!(label.to_string(), "Animation2");
 }
 
+#[test]
+fn test_animation_graph_label() {
+    let label = FbxAssetLabel::AnimationGraph(0);
+    assert_eq!(label.to_string(), "AnimationGraph0");
+}
+
+#[test]
+fn test_animation_stack_labels() {
+    let label = FbxAssetLabel::AnimationStack(1);
+    assert_eq!(label.to_string(), "AnimationStack1");
+
+    let label = FbxAssetLabel::AnimationLayer(1, 2);
+    assert_eq!(label.to_string(), "Animation1Layer2");
+}
+
+#[test]
+fn test_animation_clip_label() {
+    let label = FbxAssetLabel::AnimationClip(2);
+    assert_eq!(label.to_string(), "AnimationClip2");
+}
+
+#[test]
+fn test_root_motion_labels() {
+    assert_eq!(FbxAssetLabel::RootMotion(1).to_string(), "RootMotion1");
+    assert_eq!(
+        FbxAssetLabel::AnimationClipRootMotion(3).to_string(),
+        "AnimationClip3RootMotion"
+    );
+}
+
 #[test]
 fn test_skin_label() {
     let label = FbxAssetLabel::Skin(4);
     assert_eq!(label.to_string(), "Skin4");
 }
 
+#[test]
+fn test_humanoid_label() {
+    let label = FbxAssetLabel::Humanoid(6);
+    assert_eq!(label.to_string(), "Humanoid6");
+}
+
+#[test]
+fn test_vertex_animation_label() {
+    let label = FbxAssetLabel::VertexAnimation(5);
+    assert_eq!(label.to_string(), "VertexAnimation5");
+}
+
 #[test]
 fn test_default_material_label() {
     let label = FbxAssetLabel::DefaultMaterial;
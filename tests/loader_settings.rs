@@ -1,7 +1,11 @@
 //! Tests for FBX loader settings.
 
 use bevy::asset::RenderAssetUsages;
-use bevy_ufbx::FbxLoaderSettings;
+use bevy_ufbx::{
+    FbxAnimationSettings, FbxClipSettings, FbxKeyReductionSettings, FbxLoaderSettings,
+    FbxRetargetSettings, FbxSkinningSettings,
+};
+use std::collections::HashMap;
 
 #[test]
 fn test_loader_settings_default() {
@@ -13,6 +17,21 @@ fn test_loader_settings_default() {
     assert!(settings.load_lights);
     assert!(!settings.include_source);
     assert!(!settings.convert_coordinates);
+    assert!(!settings.animation_only);
+    assert!(settings.autoplay_animation.is_none());
+    assert_eq!(settings.animation.sample_rate, 30.0);
+    assert!(settings.animation.bake_layers);
+    assert_eq!(settings.animation.resample_tolerance, 0.0);
+    assert!(settings.animation.preserve_interpolation);
+    assert!(settings.animation.clips.is_empty());
+    assert!(settings.animation.root_motion_joint.is_none());
+    assert!(settings.animation.target_skeleton.is_none());
+    assert!(settings.animation.retarget.is_none());
+    assert_eq!(settings.animation.event_prefix, "Event");
+    assert!(!settings.animation.bake_constraints);
+    assert!(settings.animation.key_reduction.is_none());
+    assert_eq!(settings.skinning.max_influences, 4);
+    assert_eq!(settings.skinning.min_weight, 0.0);
 }
 
 #[test]
@@ -24,6 +43,37 @@ fn test_loader_settings_custom() {
         load_lights: false,
         include_source: true,
         convert_coordinates: true,
+        animation_only: true,
+        autoplay_animation: Some(1),
+        animation: FbxAnimationSettings {
+            sample_rate: 60.0,
+            bake_layers: false,
+            resample_tolerance: 0.001,
+            preserve_interpolation: false,
+            clips: vec![FbxClipSettings {
+                name: "Walk".to_string(),
+                stack: 0,
+                start_frame: 10,
+                end_frame: 40,
+                looping: true,
+            }],
+            root_motion_joint: Some("Hips".to_string()),
+            target_skeleton: Some("character.fbx".to_string()),
+            retarget: Some(FbxRetargetSettings {
+                bone_map: HashMap::from([("mixamorig:Hips".to_string(), "pelvis".to_string())]),
+                ..Default::default()
+            }),
+            event_prefix: "Notify".to_string(),
+            bake_constraints: true,
+            key_reduction: Some(FbxKeyReductionSettings {
+                rotation: 0.5,
+                ..Default::default()
+            }),
+        },
+        skinning: FbxSkinningSettings {
+            max_influences: 2,
+            min_weight: 0.01,
+        },
     };
 
     assert_eq!(settings.load_meshes, RenderAssetUsages::RENDER_WORLD);
@@ -32,6 +82,35 @@ fn test_loader_settings_custom() {
     assert!(!settings.load_lights);
     assert!(settings.include_source);
     assert!(settings.convert_coordinates);
+    assert!(settings.animation_only);
+    assert_eq!(settings.autoplay_animation, Some(1));
+    assert_eq!(settings.animation.sample_rate, 60.0);
+    assert!(!settings.animation.bake_layers);
+    assert!(!settings.animation.preserve_interpolation);
+    assert_eq!(settings.animation.clips[0].name, "Walk");
+    assert!(settings.animation.clips[0].looping);
+    assert_eq!(
+        settings.animation.root_motion_joint.as_deref(),
+        Some("Hips")
+    );
+    assert_eq!(
+        settings.animation.target_skeleton.as_deref(),
+        Some("character.fbx")
+    );
+    let retarget = settings.animation.retarget.as_ref().unwrap();
+    assert_eq!(retarget.bone_map["mixamorig:Hips"], "pelvis");
+    assert_eq!(settings.animation.event_prefix, "Notify");
+    assert!(settings.animation.bake_constraints);
+    assert_eq!(
+        settings
+            .animation
+            .key_reduction
+            .as_ref()
+            .map(|reduction| reduction.rotation),
+        Some(0.5)
+    );
+    assert_eq!(settings.skinning.max_influences, 2);
+    assert_eq!(settings.skinning.min_weight, 0.01);
 }
 
 #[test]
@@ -43,6 +122,13 @@ fn test_loader_settings_serialization() {
         load_lights: true,
         include_source: false,
         convert_coordinates: true,
+        animation_only: false,
+        autoplay_animation: Some(0),
+        animation: FbxAnimationSettings {
+            sample_rate: 24.0,
+            ..Default::default()
+        },
+        skinning: FbxSkinningSettings::default(),
     };
 
     // Serialize
@@ -62,4 +148,26 @@ fn test_loader_settings_serialization() {
         deserialized.convert_coordinates,
         original.convert_coordinates
     );
+    assert_eq!(deserialized.animation_only, original.animation_only);
+    assert_eq!(deserialized.autoplay_animation, original.autoplay_animation);
+    assert_eq!(
+        deserialized.animation.sample_rate,
+        original.animation.sample_rate
+    );
+}
+
+#[test]
+fn test_clip_settings_deserialization() {
+    let settings: FbxLoaderSettings = serde_json::from_str(
+        r#"{"animation": {"clips": [{"name": "Run", "start_frame": 41, "end_frame": 60, "loop": true}]}}"#,
+    )
+    .expect("Failed to deserialize");
+
+    let clip = &settings.animation.clips[0];
+    assert_eq!(clip.name, "Run");
+    assert_eq!(clip.stack, 0);
+    assert_eq!(clip.start_frame, 41);
+    assert_eq!(clip.end_frame, 60);
+    assert!(clip.looping);
+    assert_eq!(settings.animation.sample_rate, 30.0);
 }
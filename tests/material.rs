@@ -0,0 +1,83 @@
+//! Tests for animated material properties.
+
+use bevy::animation::AnimationTargetId;
+use bevy::ecs::system::RunSystemOnce;
+use bevy::math::Affine2;
+use bevy::prelude::*;
+use bevy_ufbx::material::{apply_fbx_material_animation, material_curve, FbxMaterialAnimation};
+use bevy_ufbx::types::{FbxKeyframes, FbxMaterialProperty, FbxMaterialTrack};
+
+#[test]
+fn test_material_curves() {
+    let mut track = FbxMaterialTrack {
+        material_index: 0,
+        target: AnimationTargetId::from_name(&Name::new("Belt")),
+        property: FbxMaterialProperty::UvOffset,
+        keyframes: FbxKeyframes {
+            times: vec![0.0, 2.0],
+            values: vec![Vec3::ZERO, Vec3::new(1.0, 0.0, 0.0)],
+            ..Default::default()
+        },
+    };
+
+    for property in [
+        FbxMaterialProperty::BaseColor,
+        FbxMaterialProperty::EmissiveColor,
+        FbxMaterialProperty::UvOffset,
+        FbxMaterialProperty::UvScale,
+        FbxMaterialProperty::UvRotation,
+    ] {
+        track.property = property;
+        assert!(material_curve(&track).is_some());
+    }
+}
+
+#[test]
+fn test_material_animation_uv_transform() {
+    let animation = FbxMaterialAnimation {
+        base_color: LinearRgba::WHITE,
+        emissive: LinearRgba::BLACK,
+        uv_offset: Vec2::new(0.25, 0.0),
+        uv_scale: Vec2::ONE,
+        uv_rotation: 0.0,
+        animated: vec![FbxMaterialProperty::UvOffset],
+    };
+
+    assert_eq!(
+        animation.uv_transform(),
+        Affine2::from_translation(Vec2::new(0.25, 0.0))
+    );
+}
+
+#[test]
+fn test_apply_material_animation_only_writes_animated_properties() {
+    let mut world = World::new();
+    let mut materials = Assets::<StandardMaterial>::default();
+    let uv_transform = Affine2::from_scale(Vec2::splat(2.0));
+    let handle = materials.add(StandardMaterial {
+        base_color: Color::linear_rgba(1.0, 1.0, 1.0, 0.5),
+        emissive: LinearRgba::BLUE,
+        uv_transform,
+        ..Default::default()
+    });
+    world.insert_resource(materials);
+    world.spawn((
+        MeshMaterial3d(handle.clone()),
+        FbxMaterialAnimation {
+            base_color: LinearRgba::RED,
+            emissive: LinearRgba::BLACK,
+            uv_offset: Vec2::ZERO,
+            uv_scale: Vec2::ONE,
+            uv_rotation: 0.0,
+            animated: vec![FbxMaterialProperty::BaseColor],
+        },
+    ));
+
+    world.run_system_once(apply_fbx_material_animation).unwrap();
+
+    let materials = world.resource::<Assets<StandardMaterial>>();
+    let material = materials.get(&handle).unwrap();
+    assert_eq!(material.base_color, Color::linear_rgba(1.0, 0.0, 0.0, 0.5));
+    assert_eq!(material.emissive, LinearRgba::BLUE);
+    assert_eq!(material.uv_transform, uv_transform);
+}
@@ -0,0 +1,92 @@
+//! Tests for mesh conversion helpers.
+
+use bevy_ufbx::mesh::{compact_joint_palette, inbetween_weights, limit_influences, FbxSkinWeights};
+
+#[test]
+fn test_inbetween_weights_single_shape() {
+    assert_eq!(inbetween_weights(&[1.0], 0.25), vec![0.25]);
+    assert_eq!(inbetween_weights(&[1.0], 0.0), vec![0.0]);
+}
+
+#[test]
+fn test_inbetween_weights_cross_fade() {
+    let targets = [0.5, 1.0];
+
+    assert_eq!(inbetween_weights(&targets, 0.25), vec![0.5, 0.0]);
+    assert_eq!(inbetween_weights(&targets, 0.5), vec![1.0, 0.0]);
+    assert_eq!(inbetween_weights(&targets, 0.75), vec![0.5, 0.5]);
+    assert_eq!(inbetween_weights(&targets, 1.0), vec![0.0, 1.0]);
+}
+
+#[test]
+fn test_inbetween_weights_empty_channel() {
+    assert!(inbetween_weights(&[], 1.0).is_empty());
+}
+
+#[test]
+fn test_limit_influences_keeps_largest() {
+    let mut influences = vec![(0, 0.1), (1, 0.4), (2, 0.05), (3, 0.2), (4, 0.25)];
+
+    let (joints, weights, dropped) = limit_influences(&mut influences, 4, 0.0);
+
+    assert_eq!(joints, [1, 4, 3, 0]);
+    assert_eq!(dropped, 0.05);
+    assert!((weights.iter().sum::<f32>() - 1.0).abs() < 1e-6);
+    assert!((weights[0] - 0.4 / 0.95).abs() < 1e-6);
+}
+
+#[test]
+fn test_limit_influences_prunes_small_weights() {
+    let mut influences = vec![(5, 0.6), (6, 0.005), (7, 0.3)];
+
+    let (joints, weights, dropped) = limit_influences(&mut influences, 4, 0.01);
+
+    assert_eq!(joints, [5, 7, 0, 0]);
+    assert_eq!(weights[2..], [0.0, 0.0]);
+    assert!((weights[0] - 2.0 / 3.0).abs() < 1e-6);
+    // Pruned weights are not reported as dropped by the limit
+    assert_eq!(dropped, 0.0);
+}
+
+#[test]
+fn test_limit_influences_keeps_largest_when_all_pruned() {
+    let mut influences = vec![(1, 0.001), (2, 0.001), (3, 0.002)];
+
+    let (joints, weights, dropped) = limit_influences(&mut influences, 2, 0.01);
+
+    assert_eq!(joints, [3, 1, 0, 0]);
+    assert!((weights[0] - 2.0 / 3.0).abs() < 1e-6);
+    assert!((weights[1] - 1.0 / 3.0).abs() < 1e-6);
+    // The rest are below the minimum weight, so pruned rather than dropped
+    assert_eq!(dropped, 0.0);
+}
+
+#[test]
+fn test_limit_influences_binds_unweighted_vertices() {
+    let (joints, weights, _) = limit_influences(&mut Vec::new(), 4, 0.0);
+    assert_eq!(joints, [0; 4]);
+    assert_eq!(weights, [1.0, 0.0, 0.0, 0.0]);
+
+    let (joints, weights, _) = limit_influences(&mut vec![(5, 0.0)], 4, 0.0);
+    assert_eq!(joints, [5, 0, 0, 0]);
+    assert_eq!(weights, [1.0, 0.0, 0.0, 0.0]);
+}
+
+#[test]
+fn test_compact_joint_palette() {
+    let skin_weights = FbxSkinWeights {
+        joint_indices: vec![[7, 3, 0, 0], [12, 0, 0, 0], [3, 9, 0, 0]],
+        joint_weights: vec![
+            [0.5, 0.5, 0.0, 0.0],
+            [1.0, 0.0, 0.0, 0.0],
+            [0.75, 0.25, 0.0, 0.0],
+        ],
+        ..Default::default()
+    };
+
+    // Vertex 1 belongs to another material group
+    let (joints, joint_indices) = compact_joint_palette(&skin_weights, &[0, 2, 2]);
+
+    assert_eq!(joints, vec![3, 7, 9]);
+    assert_eq!(joint_indices, vec![[1, 0, 0, 0], [0; 4], [0, 2, 0, 0]]);
+}
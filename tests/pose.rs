@@ -0,0 +1,100 @@
+//! Tests for pose sampling.
+
+use bevy::animation::AnimationTargetId;
+use bevy::prelude::*;
+use bevy_ufbx::pose::FbxPoseData;
+use bevy_ufbx::types::{FbxAnimation, FbxInterpolation, FbxKeyframes, FbxMorphTrack, FbxNodeTrack};
+use std::collections::HashMap;
+
+/// Root with a child one unit up, moving along X over one second.
+fn pose_data() -> FbxPoseData {
+    let target = AnimationTargetId::from_name(&Name::new("Child"));
+    FbxPoseData {
+        parents: vec![None, Some(0)],
+        rest: vec![
+            Transform::from_xyz(0.0, 0.0, 5.0),
+            Transform::from_xyz(0.0, 1.0, 0.0),
+        ],
+        morph_weights: HashMap::from([(1, vec![0.0, 0.0])]),
+        animations: vec![FbxAnimation {
+            name: "Move".to_string(),
+            duration: 1.0,
+            tracks: vec![FbxNodeTrack {
+                node_index: 1,
+                target,
+                translation: FbxKeyframes {
+                    times: vec![0.0, 1.0],
+                    values: vec![Vec3::ZERO, Vec3::new(2.0, 0.0, 0.0)],
+                    ..Default::default()
+                },
+                rotation: FbxKeyframes::default(),
+                scale: FbxKeyframes::default(),
+            }],
+            morph_tracks: vec![FbxMorphTrack {
+                node_index: 1,
+                target,
+                times: vec![0.0, 1.0],
+                weights: vec![0.0, 1.0, 1.0, 0.0],
+            }],
+            property_tracks: Vec::new(),
+            material_tracks: Vec::new(),
+            root_motion: None,
+            markers: Vec::new(),
+        }],
+        source: None,
+    }
+}
+
+#[test]
+fn test_sample_pose_from_curves() {
+    let pose = pose_data().sample(0, 0.25).unwrap();
+
+    assert_eq!(pose.local[0], Transform::from_xyz(0.0, 0.0, 5.0));
+    assert_eq!(pose.local[1].translation, Vec3::new(0.5, 0.0, 0.0));
+    assert_eq!(
+        pose.world[1].transform_point3(Vec3::ZERO),
+        Vec3::new(0.5, 0.0, 5.0)
+    );
+    assert_eq!(pose.morph_weights[&1], vec![0.25, 0.75]);
+}
+
+#[test]
+fn test_sample_pose_holds_last_key() {
+    let pose = pose_data().sample(0, 3.0).unwrap();
+
+    assert_eq!(pose.local[1].translation, Vec3::new(2.0, 0.0, 0.0));
+    assert_eq!(pose.morph_weights[&1], vec![1.0, 0.0]);
+}
+
+#[test]
+fn test_sample_pose_follows_cubic_keys() {
+    let mut data = pose_data();
+    let translation = &mut data.animations[0].tracks[0].translation;
+    translation.interpolation = FbxInterpolation::Cubic;
+    translation.tangents = vec![(Vec3::ZERO, Vec3::ZERO); 2];
+
+    // Flat tangents ease in, so a quarter of the way the child has moved
+    // less than a quarter of the distance
+    let pose = data.sample(0, 0.25).unwrap();
+    assert!(pose.local[1]
+        .translation
+        .abs_diff_eq(Vec3::new(0.3125, 0.0, 0.0), 1e-5));
+}
+
+#[test]
+fn test_sample_pose_missing_stack() {
+    assert!(pose_data().sample(1, 0.0).is_none());
+}
+
+#[test]
+fn test_sample_pose_from_source() {
+    let root = ufbx::load_file("assets/cube.fbx", ufbx::LoadOpts::default()).unwrap();
+    assert!(!root.anim_stacks.is_empty());
+    let node_count = root.nodes.len();
+    let mut data = FbxPoseData::new(&root, Vec::new());
+    data.source = Some(root.into());
+
+    let pose = data.sample(0, 0.0).unwrap();
+    assert_eq!(pose.local.len(), node_count);
+    assert_eq!(pose.world.len(), node_count);
+}
@@ -0,0 +1,84 @@
+//! Tests for animated visibility, custom, camera and light properties.
+
+use bevy::animation::AnimationTargetId;
+use bevy::prelude::*;
+use bevy_ufbx::property::{
+    focal_length_to_fov, horizontal_to_vertical_fov, light_intensity, property_curve, spot_angle,
+    FbxUserProperties, FbxVisibility,
+};
+use bevy_ufbx::types::{FbxAnimatedProperty, FbxKeyframes, FbxLightType, FbxPropertyTrack};
+use std::f64::consts::FRAC_PI_2;
+
+#[test]
+fn test_focal_length_to_fov() {
+    // A 12mm lens across a 24mm aperture sees 90 degrees
+    let fov = focal_length_to_fov(12.0, 24.0).unwrap();
+    assert!((fov - FRAC_PI_2).abs() < 1e-9);
+    assert_eq!(focal_length_to_fov(0.0, 24.0), None);
+}
+
+#[test]
+fn test_horizontal_to_vertical_fov() {
+    assert!((horizontal_to_vertical_fov(1.0, 1.0) - 1.0).abs() < 1e-9);
+    assert!(horizontal_to_vertical_fov(1.0, 16.0 / 9.0) < 1.0);
+}
+
+#[test]
+fn test_light_units() {
+    assert_eq!(light_intensity(FbxLightType::Directional, 1.0), 10000.0);
+    assert_eq!(light_intensity(FbxLightType::Point, 1.0), 1000.0);
+    assert!((spot_angle(90.0) - std::f32::consts::FRAC_PI_4).abs() < 1e-6);
+}
+
+#[test]
+fn test_property_curve() {
+    let mut track = FbxPropertyTrack {
+        node_index: 3,
+        target: AnimationTargetId::from_name(&Name::new("Key")),
+        property: FbxAnimatedProperty::LightColor(FbxLightType::Spot),
+        keyframes: FbxKeyframes {
+            times: vec![0.0, 1.0],
+            values: vec![Vec3::ONE, Vec3::X],
+            ..Default::default()
+        },
+    };
+    assert!(property_curve(&track).is_some());
+
+    track.property = FbxAnimatedProperty::FieldOfView;
+    assert!(property_curve(&track).is_some());
+
+    track.keyframes = FbxKeyframes::default();
+    assert!(property_curve(&track).is_none());
+}
+
+#[test]
+fn test_visibility_threshold() {
+    assert!(FbxVisibility::new(true).is_visible());
+    assert!(!FbxVisibility::new(false).is_visible());
+    assert!(FbxVisibility { value: 0.5 }.is_visible());
+    assert!(!FbxVisibility { value: 0.49 }.is_visible());
+}
+
+#[test]
+fn test_user_property_curves() {
+    let mut track = FbxPropertyTrack {
+        node_index: 1,
+        target: AnimationTargetId::from_name(&Name::new("Sword")),
+        property: FbxAnimatedProperty::UserProperty("weaponTrail".to_string()),
+        keyframes: FbxKeyframes {
+            times: vec![0.0, 0.5],
+            values: vec![Vec3::ZERO, Vec3::X],
+            ..Default::default()
+        },
+    };
+    assert!(property_curve(&track).is_some());
+
+    track.property = FbxAnimatedProperty::Visibility;
+    assert!(property_curve(&track).is_some());
+
+    let properties = FbxUserProperties {
+        values: [("weaponTrail".to_string(), 1.0)].into_iter().collect(),
+    };
+    assert_eq!(properties.get("weaponTrail"), Some(1.0));
+    assert_eq!(properties.get("missing"), None);
+}
@@ -0,0 +1,128 @@
+//! Tests for keyframe reduction.
+
+use bevy::animation::AnimationTargetId;
+use bevy::prelude::*;
+use bevy_ufbx::reduction::{reduce_animation, reduce_keyframes, FbxKeyReduction};
+use bevy_ufbx::types::{FbxAnimation, FbxInterpolation, FbxKeyframes, FbxNodeTrack};
+use bevy_ufbx::FbxKeyReductionSettings;
+
+fn keyframes<T>(values: Vec<T>) -> FbxKeyframes<T> {
+    FbxKeyframes {
+        times: (0..values.len())
+            .map(|index| index as f32 / 120.0)
+            .collect(),
+        values,
+        interpolation: FbxInterpolation::Linear,
+        tangents: Vec::new(),
+    }
+}
+
+#[test]
+fn test_reduce_linear_keeps_endpoints_and_corners() {
+    // Straight ramp up to key 3, then flat
+    let mut translation = keyframes(
+        [0.0, 1.0, 2.0, 3.0, 3.0, 3.0, 3.0]
+            .map(|x| Vec3::new(x, 0.0, 0.0))
+            .to_vec(),
+    );
+
+    reduce_keyframes(&mut translation, 0.001, |a, b| a.distance(b));
+
+    assert_eq!(translation.times, vec![0.0, 3.0 / 120.0, 6.0 / 120.0]);
+    assert_eq!(translation.values[1], Vec3::new(3.0, 0.0, 0.0));
+}
+
+#[test]
+fn test_reduce_keeps_keys_outside_tolerance() {
+    let mut translation = keyframes(vec![Vec3::ZERO, Vec3::new(0.0, 0.01, 0.0), Vec3::ZERO]);
+
+    reduce_keyframes(&mut translation, 0.001, |a, b| a.distance(b));
+
+    assert_eq!(translation.values.len(), 3);
+}
+
+#[test]
+fn test_reduce_stepped_drops_held_values() {
+    let mut translation = FbxKeyframes {
+        interpolation: FbxInterpolation::Constant,
+        ..keyframes(
+            [0.0, 0.0, 1.0, 1.0, 1.0]
+                .map(|x| Vec3::new(x, 0.0, 0.0))
+                .to_vec(),
+        )
+    };
+
+    reduce_keyframes(&mut translation, 0.001, |a, b| a.distance(b));
+
+    assert_eq!(translation.times, vec![0.0, 2.0 / 120.0, 4.0 / 120.0]);
+}
+
+#[test]
+fn test_reduce_animation_reports_ratio() {
+    let rotations = (0..9)
+        .map(|index| Quat::from_rotation_y(index as f32 * 0.1))
+        .collect();
+    let mut animation = FbxAnimation {
+        name: "Turn".to_string(),
+        duration: 8.0 / 120.0,
+        tracks: vec![FbxNodeTrack {
+            node_index: 1,
+            target: AnimationTargetId::from_name(&Name::new("Hips")),
+            translation: keyframes(vec![Vec3::ONE; 9]),
+            rotation: keyframes(rotations),
+            scale: FbxKeyframes::default(),
+        }],
+        morph_tracks: Vec::new(),
+        property_tracks: Vec::new(),
+        material_tracks: Vec::new(),
+        root_motion: None,
+        markers: Vec::new(),
+    };
+
+    let reduction = reduce_animation(&mut animation, &FbxKeyReductionSettings::default(), &[]);
+
+    assert_eq!(
+        reduction,
+        FbxKeyReduction {
+            keys_before: 18,
+            keys_after: 4,
+        }
+    );
+    assert_eq!(reduction.ratio(), 4.5);
+}
+
+#[test]
+fn test_reduce_animation_in_file_units() {
+    // Half a millimeter of noise on a track keyed in centimeters
+    let translation: Vec<Vec3> = (0..9)
+        .map(|index| Vec3::new(0.0, 0.05 * (index % 2) as f32, 0.0))
+        .collect();
+    let animation = FbxAnimation {
+        name: "Idle".to_string(),
+        duration: 8.0 / 120.0,
+        tracks: vec![FbxNodeTrack {
+            node_index: 1,
+            target: AnimationTargetId::from_name(&Name::new("Hips")),
+            translation: keyframes(translation),
+            rotation: FbxKeyframes::default(),
+            scale: FbxKeyframes::default(),
+        }],
+        morph_tracks: Vec::new(),
+        property_tracks: Vec::new(),
+        material_tracks: Vec::new(),
+        root_motion: None,
+        markers: Vec::new(),
+    };
+    let settings = FbxKeyReductionSettings {
+        translation: 0.001,
+        ..Default::default()
+    };
+
+    let mut in_meters = animation.clone();
+    reduce_animation(&mut in_meters, &settings, &[1.0, 1.0]);
+    assert_eq!(in_meters.tracks[0].translation.times.len(), 9);
+
+    let mut in_centimeters = animation;
+    reduce_animation(&mut in_centimeters, &settings, &[1.0, 0.01]);
+    assert_eq!(in_centimeters.tracks[0].translation.times.len(), 2);
+}
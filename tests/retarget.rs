@@ -0,0 +1,248 @@
+//! Tests for binding animations to another node hierarchy.
+
+use bevy::animation::AnimationTargetId;
+use bevy::prelude::*;
+use bevy_ufbx::retarget::{
+    bind_animation, retarget_animation, skeleton_rest_pose, strip_namespace, Retargeter,
+};
+use bevy_ufbx::types::{
+    FbxAnimation, FbxJointKind, FbxKeyframes, FbxMaterialProperty, FbxMaterialTrack, FbxNodeTrack,
+    FbxRestBone, Skeleton,
+};
+use bevy_ufbx::FbxRetargetSettings;
+use std::collections::{BTreeSet, HashMap};
+
+fn track(node_index: usize, name: &str) -> FbxNodeTrack {
+    FbxNodeTrack {
+        node_index,
+        target: AnimationTargetId::from_name(&Name::new(name.to_string())),
+        translation: FbxKeyframes::default(),
+        rotation: FbxKeyframes::default(),
+        scale: FbxKeyframes::default(),
+    }
+}
+
+fn material_track(name: &str) -> FbxMaterialTrack {
+    FbxMaterialTrack {
+        material_index: 0,
+        target: AnimationTargetId::from_names(
+            [Name::new("Material"), Name::new(name.to_string())].iter(),
+        ),
+        property: FbxMaterialProperty::EmissiveColor,
+        keyframes: FbxKeyframes::default(),
+    }
+}
+
+#[test]
+fn test_strip_namespace() {
+    assert_eq!(strip_namespace("mixamorig:Hips"), "Hips");
+    assert_eq!(strip_namespace("Rig:mixamorig:Spine"), "Spine");
+    assert_eq!(strip_namespace("Hips"), "Hips");
+}
+
+#[test]
+fn test_bind_animation_retargets_and_reports() {
+    let mut animation = FbxAnimation {
+        name: "Run".to_string(),
+        duration: 1.0,
+        tracks: vec![track(1, "Hips"), track(2, "Tail")],
+        morph_tracks: Vec::new(),
+        property_tracks: Vec::new(),
+        material_tracks: vec![material_track("Visor")],
+        root_motion: None,
+        markers: Vec::new(),
+    };
+    let hips =
+        AnimationTargetId::from_names([Name::new("Armature"), Name::new("mixamorig:Hips")].iter());
+    let targets = vec![None, Some(hips), None];
+    let mut unmatched = BTreeSet::new();
+
+    bind_animation(&mut animation, &targets, &mut unmatched);
+
+    assert_eq!(animation.tracks.len(), 1);
+    assert_eq!(animation.tracks[0].target, hips);
+    assert_eq!(unmatched.into_iter().collect::<Vec<_>>(), vec![2]);
+    // Materials are not bound to nodes
+    assert_eq!(animation.material_tracks.len(), 1);
+    assert_eq!(
+        animation.material_tracks[0].target,
+        material_track("Visor").target
+    );
+}
+
+fn rest_bone(name: &str, parent: Option<&str>, local: Transform, height: f32) -> FbxRestBone {
+    FbxRestBone {
+        target: AnimationTargetId::from_name(&Name::new(name.to_string())),
+        parent: parent.map(str::to_string),
+        local,
+        parent_world: Mat4::IDENTITY,
+        world_translation: Vec3::Y * height,
+    }
+}
+
+#[test]
+fn test_retarget_rotation_and_root_translation() {
+    let source = HashMap::from([
+        (
+            "Hips".to_string(),
+            rest_bone("Hips", None, Transform::from_xyz(0.0, 1.0, 0.0), 1.0),
+        ),
+        (
+            "Spine".to_string(),
+            rest_bone("Spine", Some("Hips"), Transform::default(), 1.2),
+        ),
+    ]);
+    let pelvis_rest = Transform::from_xyz(0.0, 2.0, 0.0)
+        .with_rotation(Quat::from_rotation_x(std::f32::consts::FRAC_PI_2));
+    let target = HashMap::from([
+        (
+            "pelvis".to_string(),
+            rest_bone("pelvis", None, pelvis_rest, 2.0),
+        ),
+        (
+            "Spine".to_string(),
+            rest_bone("Spine", Some("pelvis"), Transform::default(), 2.4),
+        ),
+    ]);
+    let settings = FbxRetargetSettings {
+        bone_map: HashMap::from([("Hips".to_string(), "pelvis".to_string())]),
+        ..Default::default()
+    };
+    let names = vec!["Hips".to_string(), "Spine".to_string(), "Tail".to_string()];
+    let retargeter = Retargeter::new(source, target, &names, &settings);
+
+    let mut animation = FbxAnimation {
+        name: "Walk".to_string(),
+        duration: 1.0,
+        tracks: vec![
+            FbxNodeTrack {
+                translation: FbxKeyframes {
+                    times: vec![0.0, 1.0],
+                    values: vec![Vec3::Y, Vec3::new(1.0, 1.0, 0.0)],
+                    ..Default::default()
+                },
+                rotation: FbxKeyframes {
+                    times: vec![0.0],
+                    values: vec![Quat::from_rotation_y(0.5)],
+                    ..Default::default()
+                },
+                ..track(0, "Hips")
+            },
+            FbxNodeTrack {
+                translation: FbxKeyframes {
+                    times: vec![0.0],
+                    values: vec![Vec3::Y * 0.3],
+                    ..Default::default()
+                },
+                ..track(1, "Spine")
+            },
+            track(2, "Tail"),
+        ],
+        morph_tracks: Vec::new(),
+        property_tracks: Vec::new(),
+        material_tracks: vec![material_track("Visor")],
+        root_motion: None,
+        markers: Vec::new(),
+    };
+    let mut unmatched = BTreeSet::new();
+
+    retargeter.retarget(&mut animation, &mut unmatched);
+
+    assert_eq!(unmatched.into_iter().collect::<Vec<_>>(), vec![2]);
+    let pelvis = &animation.tracks[0];
+    assert_eq!(
+        pelvis.target,
+        AnimationTargetId::from_name(&Name::new("pelvis"))
+    );
+    assert!(pelvis.translation.values[1].abs_diff_eq(Vec3::new(2.0, 2.0, 0.0), 1e-5));
+    let expected = Quat::from_rotation_y(0.5) * pelvis_rest.rotation;
+    assert!(pelvis.rotation.values[0].abs_diff_eq(expected, 1e-5));
+
+    // Bones below the root keep the proportions of the target rig
+    assert!(animation.tracks[1].translation.values.is_empty());
+    assert_eq!(animation.material_tracks.len(), 1);
+}
+
+/// Two joint chain, with the root at `height` and its nodes at `nodes`.
+fn skeleton(names: [&str; 2], nodes: [usize; 2], height: f32) -> Skeleton {
+    let rest_transforms = vec![
+        Transform::from_xyz(0.0, height, 0.0),
+        Transform::from_xyz(0.0, 0.5, 0.0),
+    ];
+    Skeleton {
+        index: 0,
+        name: names[0].to_string(),
+        joint_names: names.iter().map(|name| name.to_string()).collect(),
+        parents: vec![None, Some(0)],
+        bind_matrices: vec![
+            rest_transforms[0].to_matrix(),
+            rest_transforms[0].to_matrix() * rest_transforms[1].to_matrix(),
+        ],
+        rest_transforms,
+        kinds: vec![FbxJointKind::LimbNode; 2],
+        nodes: nodes.to_vec(),
+        targets: names
+            .iter()
+            .map(|name| AnimationTargetId::from_name(&Name::new(name.to_string())))
+            .collect(),
+        humanoid: None,
+    }
+}
+
+#[test]
+fn test_retarget_between_loaded_skeletons() {
+    let source = skeleton(["Hips", "Spine"], [1, 2], 1.0);
+    let target = skeleton(["pelvis", "spine_01"], [4, 5], 2.0);
+
+    let rest = skeleton_rest_pose(&source);
+    assert_eq!(rest["Spine"].parent.as_deref(), Some("Hips"));
+    assert!(rest["Spine"]
+        .local
+        .translation
+        .abs_diff_eq(Vec3::new(0.0, 0.5, 0.0), 1e-5));
+    assert!(rest["Hips"].world_translation.abs_diff_eq(Vec3::Y, 1e-5));
+
+    let settings = FbxRetargetSettings {
+        bone_map: HashMap::from([
+            ("Hips".to_string(), "pelvis".to_string()),
+            ("Spine".to_string(), "spine_01".to_string()),
+        ]),
+        ..Default::default()
+    };
+    let animation = FbxAnimation {
+        name: "Walk".to_string(),
+        duration: 1.0,
+        tracks: vec![
+            FbxNodeTrack {
+                translation: FbxKeyframes {
+                    times: vec![0.0, 1.0],
+                    values: vec![Vec3::Y, Vec3::new(1.0, 1.0, 0.0)],
+                    ..Default::default()
+                },
+                ..track(1, "Hips")
+            },
+            FbxNodeTrack {
+                rotation: FbxKeyframes {
+                    times: vec![0.0],
+                    values: vec![Quat::from_rotation_z(0.5)],
+                    ..Default::default()
+                },
+                ..track(2, "Spine")
+            },
+            track(3, "Prop"),
+        ],
+        morph_tracks: Vec::new(),
+        property_tracks: Vec::new(),
+        material_tracks: Vec::new(),
+        root_motion: None,
+        markers: Vec::new(),
+    };
+    let mut unmatched = BTreeSet::new();
+
+    let clip = retarget_animation(&animation, &source, &target, &settings, &mut unmatched);
+
+    assert_eq!(unmatched.into_iter().collect::<Vec<_>>(), vec![3]);
+    assert!(clip.curves_for_target(target.targets[0]).is_some());
+    assert!(clip.curves_for_target(target.targets[1]).is_some());
+    assert!(clip.curves_for_target(source.targets[0]).is_none());
+}
@@ -0,0 +1,105 @@
+//! Tests for root motion extraction.
+
+use bevy::animation::AnimationTargetId;
+use bevy::prelude::*;
+use bevy_ufbx::root_motion::{extract_root_motion, yaw};
+use bevy_ufbx::types::{FbxAnimation, FbxKeyframes, FbxNodeTrack};
+
+fn walk_animation() -> FbxAnimation {
+    FbxAnimation {
+        name: "Walk".to_string(),
+        duration: 1.0,
+        tracks: vec![FbxNodeTrack {
+            node_index: 2,
+            target: AnimationTargetId::from_name(&Name::new("Hips")),
+            translation: FbxKeyframes {
+                times: vec![0.0, 1.0],
+                values: vec![Vec3::new(0.0, 1.0, 0.0), Vec3::new(2.0, 1.5, 3.0)],
+                ..Default::default()
+            },
+            rotation: FbxKeyframes {
+                times: vec![0.0, 1.0],
+                values: vec![
+                    Quat::from_rotation_x(0.2),
+                    Quat::from_rotation_y(1.0) * Quat::from_rotation_x(0.2),
+                ],
+                ..Default::default()
+            },
+            scale: FbxKeyframes::default(),
+        }],
+        morph_tracks: Vec::new(),
+        property_tracks: Vec::new(),
+        material_tracks: Vec::new(),
+        root_motion: None,
+        markers: Vec::new(),
+    }
+}
+
+#[test]
+fn test_extract_root_motion_keeps_height_and_tilt() {
+    let mut animation = walk_animation();
+
+    let root_motion =
+        extract_root_motion(&mut animation, 2, Mat4::IDENTITY).expect("Hips are animated");
+
+    let track = &animation.tracks[0];
+    assert_eq!(
+        track.translation.values,
+        vec![Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 1.5, 0.0)]
+    );
+    assert!(track.rotation.values[1].angle_between(Quat::from_rotation_x(0.2)) < 1e-5);
+
+    assert_eq!(root_motion.translation.values[1], Vec3::new(2.0, 0.0, 3.0));
+    assert!(root_motion.rotation.values[1].angle_between(Quat::from_rotation_y(1.0)) < 1e-5);
+}
+
+#[test]
+fn test_extract_root_motion_under_z_up_parent() {
+    // The same walk keyed in the Z-up axes of a parent that converts to Y-up
+    let parent_to_world = Mat4::from_rotation_x(-std::f32::consts::FRAC_PI_2);
+    let to_local = Quat::from_rotation_x(std::f32::consts::FRAC_PI_2);
+    let mut animation = walk_animation();
+    let track = &mut animation.tracks[0];
+    for value in &mut track.translation.values {
+        *value = to_local * *value;
+    }
+    for value in &mut track.rotation.values {
+        *value = to_local * *value;
+    }
+
+    let root_motion =
+        extract_root_motion(&mut animation, 2, parent_to_world).expect("Hips are animated");
+
+    let track = &animation.tracks[0];
+    assert!(track.translation.values[1].abs_diff_eq(to_local * Vec3::new(0.0, 1.5, 0.0), 1e-5));
+    let tilt = to_local * Quat::from_rotation_x(0.2);
+    assert!(track.rotation.values[1].angle_between(tilt) < 1e-5);
+
+    assert!(root_motion.translation.values[1].abs_diff_eq(Vec3::new(2.0, 0.0, 3.0), 1e-5));
+    assert!(root_motion.rotation.values[1].angle_between(Quat::from_rotation_y(1.0)) < 1e-5);
+}
+
+#[test]
+fn test_extract_root_motion_missing_joint() {
+    let mut animation = walk_animation();
+    assert!(extract_root_motion(&mut animation, 7, Mat4::IDENTITY).is_none());
+}
+
+#[test]
+fn test_root_motion_delta() {
+    let mut animation = walk_animation();
+    let root_motion =
+        extract_root_motion(&mut animation, 2, Mat4::IDENTITY).expect("Hips are animated");
+
+    let delta = root_motion.delta(0.0, 0.5);
+    assert!(delta
+        .translation
+        .abs_diff_eq(Vec3::new(1.0, 0.0, 1.5), 1e-5));
+}
+
+#[test]
+fn test_yaw_of_tilted_rotation() {
+    let rotation = Quat::from_rotation_y(0.5) * Quat::from_rotation_x(0.3);
+    assert!(yaw(rotation).angle_between(Quat::from_rotation_y(0.5)) < 1e-5);
+    assert_eq!(yaw(Quat::from_rotation_x(0.3)), Quat::IDENTITY);
+}
@@ -0,0 +1,92 @@
+//! Tests for skeleton extraction.
+
+use bevy::asset::Handle;
+use bevy_ufbx::humanoid::create_humanoid;
+use bevy_ufbx::node::find_skeletons;
+use bevy_ufbx::types::{FbxJointKind, HumanoidBone};
+
+/// Hips with a null group holding the spine, next to an unrelated prop.
+const RIG: &str = r#"; FBX 7.4.0 project file
+FBXHeaderExtension:  {
+	FBXHeaderVersion: 1003
+	FBXVersion: 7400
+}
+Objects:  {
+	NodeAttribute: 2001, "NodeAttribute::Hips", "LimbNode" {
+		TypeFlags: "Skeleton"
+	}
+	NodeAttribute: 2003, "NodeAttribute::Spine", "LimbNode" {
+		TypeFlags: "Skeleton"
+	}
+	Model: 1001, "Model::Hips", "LimbNode" {
+		Version: 232
+		Properties70:  {
+			P: "Lcl Translation", "Lcl Translation", "", "A",0,100,0
+		}
+	}
+	Model: 1002, "Model::Offset", "Null" {
+		Version: 232
+	}
+	Model: 1003, "Model::Spine", "LimbNode" {
+		Version: 232
+		Properties70:  {
+			P: "Lcl Translation", "Lcl Translation", "", "A",0,10,0
+		}
+	}
+	Model: 1004, "Model::Prop", "Null" {
+		Version: 232
+	}
+}
+Connections:  {
+	C: "OO",1001,0
+	C: "OO",2001,1001
+	C: "OO",1002,1001
+	C: "OO",1003,1002
+	C: "OO",2003,1003
+	C: "OO",1004,0
+}
+"#;
+
+#[test]
+fn test_find_skeletons() {
+    let scene =
+        ufbx::load_memory(RIG.as_bytes(), ufbx::LoadOpts::default()).expect("Failed to load rig");
+
+    let skeletons = find_skeletons(&scene);
+
+    assert_eq!(skeletons.len(), 1);
+    let skeleton = &skeletons[0];
+    assert_eq!(skeleton.name, "Hips");
+    assert_eq!(skeleton.joint_names, ["Hips", "Offset", "Spine"]);
+    assert_eq!(skeleton.parents, [None, Some(0), Some(1)]);
+    assert_eq!(
+        skeleton.kinds,
+        [
+            FbxJointKind::LimbNode,
+            FbxJointKind::Null,
+            FbxJointKind::LimbNode
+        ]
+    );
+    assert_eq!(skeleton.joint_index("Spine"), Some(2));
+    assert_eq!(skeleton.rest_transforms[2].translation.y, 10.0);
+    // Unbound joints are bound at their rest pose
+    assert_eq!(skeleton.bind_matrices[2].w_axis.y, 110.0);
+}
+
+#[test]
+fn test_skeleton_humanoid_covers_every_joint() {
+    let scene =
+        ufbx::load_memory(RIG.as_bytes(), ufbx::LoadOpts::default()).expect("Failed to load rig");
+    let skeleton = &find_skeletons(&scene)[0];
+
+    let joints: Vec<_> = skeleton
+        .nodes
+        .iter()
+        .map(|&index| (&scene.nodes[index], Handle::default()))
+        .collect();
+    let humanoid = create_humanoid(&joints).expect("Hips are recognised");
+
+    assert!(humanoid.bones.contains_key(&HumanoidBone::Hips));
+    assert!(humanoid.bones.contains_key(&HumanoidBone::Spine));
+    assert!(!humanoid.complete);
+}
@@ -85,6 +85,7 @@ fn test_fbx_interpolation_equality() {
     assert_eq!(FbxInterpolation::Linear, FbxInterpolation::Linear);
     assert_eq!(FbxInterpolation::Cubic, FbxInterpolation::Cubic);
     assert_ne!(FbxInterpolation::Linear, FbxInterpolation::Cubic);
+    assert_eq!(FbxInterpolation::default(), FbxInterpolation::Linear);
 }
 
 #[test]
@@ -144,3 +145,16 @@ fn test_fbx_camera_creation() {
     assert_eq!(camera.field_of_view_deg, 60.0);
     assert!((camera.aspect_ratio - 1.777).abs() < 0.001);
 }
+
+#[test]
+fn test_fbx_skinning_warning_display() {
+    let warning = FbxSkinningWarning::UnsupportedMethod {
+        mesh: "Body".to_string(),
+        method: FbxSkinningMethod::DualQuaternion,
+    };
+
+    assert_eq!(
+        warning.to_string(),
+        "Mesh Body uses DualQuaternion skinning, which is replaced by linear blend skinning"
+    );
+}
@@ -1,7 +1,42 @@
 //! Tests for utility conversion functions.
 
 use bevy::prelude::*;
-use bevy_ufbx::utils::{convert_matrix, convert_transform};
+use bevy_ufbx::types::{FbxInterpolation, FbxSkinningMethod, Handedness};
+use bevy_ufbx::utils::{
+    convert_axes, convert_interpolation, convert_matrix, convert_quat, convert_scene_settings,
+    convert_skinning_method, convert_transform, convert_vec3,
+};
+
+/// An empty scene exported Z up, with -Y front, in inches.
+const Z_UP: &str = r#"; FBX 7.4.0 project file
+FBXHeaderExtension:  {
+	FBXHeaderVersion: 1003
+	FBXVersion: 7400
+}
+GlobalSettings:  {
+	Version: 1000
+	Properties70:  {
+		P: "UpAxis", "int", "Integer", "",2
+		P: "UpAxisSign", "int", "Integer", "",1
+		P: "FrontAxis", "int", "Integer", "",1
+		P: "FrontAxisSign", "int", "Integer", "",-1
+		P: "CoordAxis", "int", "Integer", "",0
+		P: "CoordAxisSign", "int", "Integer", "",1
+		P: "OriginalUpAxis", "int", "Integer", "",2
+		P: "OriginalUpAxisSign", "int", "Integer", "",1
+		P: "UnitScaleFactor", "double", "Number", "",2.54
+		P: "OriginalUnitScaleFactor", "double", "Number", "",2.54
+	}
+}
+Objects:  {
+	Model: 1001, "Model::Root", "Null" {
+		Version: 232
+	}
+}
+Connections:  {
+	C: "OO",1001,0
+}
+"#;
 
 #[test]
 fn test_convert_matrix() {
@@ -94,3 +129,109 @@ fn test_convert_transform_with_rotation() {
     assert!((transform.rotation.z - 0.0).abs() < 0.001);
     assert!((transform.rotation.w - half_sqrt2 as f32).abs() < 0.001);
 }
+
+#[test]
+fn test_convert_vec3_and_quat() {
+    let v = convert_vec3(&ufbx::Vec3 {
+        x: 1.0,
+        y: -2.0,
+        z: 3.5,
+    });
+    assert_eq!(v, Vec3::new(1.0, -2.0, 3.5));
+
+    let q = convert_quat(&ufbx::Quat {
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+        w: 1.0,
+    });
+    assert_eq!(q, Quat::IDENTITY);
+}
+
+#[test]
+fn test_convert_interpolation() {
+    assert_eq!(
+        convert_interpolation(ufbx::Interpolation::ConstantPrev),
+        Some(FbxInterpolation::Constant)
+    );
+    assert_eq!(
+        convert_interpolation(ufbx::Interpolation::Linear),
+        Some(FbxInterpolation::Linear)
+    );
+    assert_eq!(
+        convert_interpolation(ufbx::Interpolation::Cubic),
+        Some(FbxInterpolation::Cubic)
+    );
+    assert_eq!(
+        convert_interpolation(ufbx::Interpolation::ConstantNext),
+        None
+    );
+}
+
+#[test]
+fn test_convert_skinning_method() {
+    assert_eq!(
+        convert_skinning_method(ufbx::SkinningMethod::Linear),
+        FbxSkinningMethod::Linear
+    );
+    assert_eq!(
+        convert_skinning_method(ufbx::SkinningMethod::BlendedDqLinear),
+        FbxSkinningMethod::BlendedDqLinear
+    );
+}
+
+#[test]
+fn test_convert_axes_handedness() {
+    let right = convert_axes(&ufbx::CoordinateAxes::right_handed_y_up());
+    assert_eq!(right.up, Vec3::Y);
+    assert_eq!(right.front, Vec3::Z);
+    assert_eq!(right.handedness, Handedness::Right);
+
+    let left = convert_axes(&ufbx::CoordinateAxes::left_handed_y_up());
+    assert_eq!(left.handedness, Handedness::Left);
+
+    let z_up = convert_axes(&ufbx::CoordinateAxes::right_handed_z_up());
+    assert_eq!(z_up.up, Vec3::Z);
+    assert_eq!(z_up.handedness, Handedness::Right);
+}
+
+#[test]
+fn test_convert_scene_settings() {
+    let root = ufbx::load_file(
+        "assets/maya_cube_7400_binary.fbx",
+        ufbx::LoadOpts::default(),
+    )
+    .unwrap();
+
+    let settings = convert_scene_settings(&root);
+
+    assert_eq!(settings.frames_per_second, root.settings.frames_per_second);
+    assert!(settings.frames_per_second > 0.0);
+    assert!(settings.time_stop >= settings.time_start);
+    assert_eq!(settings.unit_meters, 0.01);
+    assert_eq!(settings.axes.handedness, Handedness::Right);
+    assert_eq!(settings.handedness, Handedness::Right);
+    assert_eq!(settings.original_front, Some(settings.axes.front));
+}
+
+#[test]
+fn test_convert_scene_settings_z_up() {
+    let root = ufbx::load_memory(
+        Z_UP.as_bytes(),
+        ufbx::LoadOpts {
+            target_unit_meters: 1.0,
+            target_axes: ufbx::CoordinateAxes::right_handed_y_up(),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let settings = convert_scene_settings(&root);
+
+    // The settings describe the file, not the converted scene
+    assert_eq!(settings.original_up, Some(Vec3::Z));
+    assert_eq!(settings.original_front, Some(Vec3::NEG_Y));
+    assert_eq!(settings.axes.up, Vec3::Z);
+    assert_eq!(settings.handedness, Handedness::Right);
+    assert!((settings.unit_meters - 0.0254).abs() < 1e-6);
+}